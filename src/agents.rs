@@ -0,0 +1,302 @@
+use crate::{
+    client, file,
+    logger::Logger,
+    models::{
+        Agent, ChatMessage, ConversationEvent, ConversationInput, ConversationRequest,
+        FunctionCall, ToolCall,
+    },
+    stream::StreamEvent,
+};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+
+/// `--conversation last` continues the most recently used conversation.
+pub const LAST_CONVERSATION: &str = "last";
+
+const TITLE_MAX_CHARS: usize = 60;
+
+/// A server-side conversation remembered locally so it can be continued.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ConversationRecord {
+    pub conversation_id: String,
+    pub agent_id: Option<String>,
+    pub title: String,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// First line of the prompt, shortened for the history list.
+pub fn conversation_title(prompt: &str) -> String {
+    let line = prompt.lines().next().unwrap_or_default().trim();
+    let mut title = line.chars().take(TITLE_MAX_CHARS).collect::<String>();
+    if title.len() < line.len() {
+        title.push('…');
+    }
+    title
+}
+
+pub async fn read_history() -> Vec<ConversationRecord> {
+    match tokio::fs::read(file::get_conversations_file_path()).await {
+        Ok(contents) => serde_json::from_slice(&contents).unwrap_or_default(),
+        Err(_) => Vec::new(),
+    }
+}
+
+pub async fn write_history(history: &[ConversationRecord]) -> std::io::Result<()> {
+    let contents = serde_json::to_vec_pretty(history)?;
+    tokio::fs::write(file::get_conversations_file_path(), contents).await
+}
+
+/// Adds the conversation or marks an existing one as used, most recent first.
+pub fn record_conversation(
+    history: &mut Vec<ConversationRecord>,
+    conversation_id: &str,
+    agent_id: Option<&str>,
+    title: &str,
+    now: u64,
+) {
+    match history
+        .iter_mut()
+        .find(|record| record.conversation_id == conversation_id)
+    {
+        Some(record) => record.updated_at = now,
+        None => history.push(ConversationRecord {
+            conversation_id: conversation_id.to_string(),
+            agent_id: agent_id.map(str::to_string),
+            title: title.to_string(),
+            created_at: now,
+            updated_at: now,
+        }),
+    }
+    history.sort_by_key(|record| std::cmp::Reverse(record.updated_at));
+}
+
+/// Resolves `last` to the most recent conversation, other ids are used as given.
+pub fn resolve_conversation_id(
+    history: &[ConversationRecord],
+    conversation_id: &str,
+) -> Result<String, String> {
+    if conversation_id != LAST_CONVERSATION {
+        return Ok(conversation_id.to_string());
+    }
+
+    history
+        .iter()
+        .max_by_key(|record| record.updated_at)
+        .map(|record| record.conversation_id.clone())
+        .ok_or_else(|| "No conversations to continue yet, start one with --agent".to_string())
+}
+
+pub fn format_agent_row(agent: &Agent) -> String {
+    let mut row = format!("{}  {}  {}", agent.id, agent.name, agent.model);
+    if !agent.tools.is_empty() {
+        let tools = agent
+            .tools
+            .iter()
+            .map(|tool| tool.r#type.as_str())
+            .collect::<Vec<&str>>();
+        row.push_str(&format!("  [{}]", tools.join(", ")));
+    }
+    if let Some(description) = agent.description.as_deref().filter(|d| !d.is_empty()) {
+        row.push_str(&format!("\n    {}", description));
+    }
+    row
+}
+
+pub fn format_history_row(record: &ConversationRecord) -> String {
+    format!(
+        "{}  {}  {}",
+        record.conversation_id,
+        record.agent_id.as_deref().unwrap_or("-"),
+        record.title
+    )
+}
+
+// takes every complete `data:` line of the server-sent events out of the buffer
+fn take_events(buffer: &mut Vec<u8>) -> Vec<Result<ConversationEvent, String>> {
+    let mut events = Vec::new();
+
+    while let Some(end) = buffer.iter().position(|byte| *byte == b'\n') {
+        let line = buffer.drain(..=end).collect::<Vec<u8>>();
+        let line = String::from_utf8_lossy(&line);
+        if let Some(data) = line.trim().strip_prefix("data:") {
+            events.push(serde_json::from_str(data.trim()).map_err(|e| e.to_string()));
+        }
+    }
+
+    events
+}
+
+/// Sends the prompt to an agent, or to `conversation_id` to continue it, streaming the
+/// answer to `sender`. The conversation is saved to the local history as soon as the
+/// server names it, so closing the UI early does not lose it.
+pub async fn stream_conversation(
+    agent_id: Option<String>,
+    conversation_id: Option<String>,
+    prompt: String,
+    image_urls: Vec<String>,
+    sender: mpsc::Sender<StreamEvent>,
+    logger: Logger,
+) -> Result<String, String> {
+    let request = ConversationRequest {
+        agent_id: agent_id.clone(),
+        inputs: vec![ConversationInput {
+            role: "user".to_string(),
+            content: ChatMessage::user_with_images(prompt.clone(), image_urls).content,
+        }],
+        stream: true,
+    };
+    let response = client::call_mistral_conversation(&request, conversation_id.as_deref(), &logger)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let logger = logger.for_stream();
+    let mut stream = response.bytes_stream();
+    let mut buffer = Vec::new();
+    let mut started_id = conversation_id;
+
+    while let Some(chunk) = stream.next().await {
+        let bytes = match chunk {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                logger.flush_stream_log().await;
+                return Err(e.to_string());
+            }
+        };
+        logger.write_stream_log(bytes.clone()).await;
+        buffer.extend_from_slice(&bytes);
+
+        for event in take_events(&mut buffer) {
+            let event = match event {
+                Ok(event) => event,
+                Err(e) => {
+                    logger
+                        .log_error(format!("Conversation event parse failed: {}", e))
+                        .await;
+                    continue;
+                }
+            };
+
+            match event {
+                ConversationEvent::Started { conversation_id } => {
+                    let mut history = read_history().await;
+                    record_conversation(
+                        &mut history,
+                        &conversation_id,
+                        agent_id.as_deref(),
+                        &conversation_title(&prompt),
+                        now_secs(),
+                    );
+                    if let Err(e) = write_history(&history).await {
+                        logger
+                            .log_error(format!("Failed to save conversation history: {}", e))
+                            .await;
+                    }
+                    started_id = Some(conversation_id);
+                }
+                ConversationEvent::MessageOutputDelta { content } => {
+                    if let Some(text) = content.text() {
+                        _ = sender.send(StreamEvent::Token(text.to_string())).await;
+                    }
+                }
+                ConversationEvent::ToolExecutionStarted { name } => {
+                    let call = ToolCall {
+                        id: String::new(),
+                        r#type: "function".to_string(),
+                        function: FunctionCall {
+                            name,
+                            arguments: String::new(),
+                        },
+                    };
+                    _ = sender.send(StreamEvent::ToolCall(call)).await;
+                }
+                ConversationEvent::Done { usage } => {
+                    if let Some(usage) = usage {
+                        _ = sender.send(StreamEvent::Usage(usage)).await;
+                    }
+                }
+                ConversationEvent::Error { message } => {
+                    logger.flush_stream_log().await;
+                    return Err(format!("Conversation failed: {}", message));
+                }
+                ConversationEvent::Other => {}
+            }
+        }
+    }
+
+    logger.flush_stream_log().await;
+
+    started_id.ok_or_else(|| "The conversation stream ended without a conversation id".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_parsed_across_chunks() {
+        let mut buffer = b"event: conversation.response.started\ndata: {\"type\": \"conversation.response.started\", \"conversation_id\": \"conv_1\"}\n\nevent: message.output.delta\ndata: {\"type\": \"message.output.delta\", \"content\": \"Hei".to_vec();
+
+        let events = take_events(&mut buffer);
+        assert_eq!(
+            events,
+            vec![Ok(ConversationEvent::Started {
+                conversation_id: "conv_1".to_string()
+            })]
+        );
+
+        buffer.extend_from_slice(b" maailma\", \"output_index\": 0}\n\ndata: {\"type\": \"agent.handoff.started\"}\ndata: {\"type\": \"message.output.delta\", \"content\": {\"type\": \"tool_reference\", \"url\": \"https://mistral.ai\"}}\n");
+        let events = take_events(&mut buffer)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect::<Vec<ConversationEvent>>();
+        assert_eq!(events.len(), 3);
+        match &events[0] {
+            ConversationEvent::MessageOutputDelta { content } => {
+                assert_eq!(content.text(), Some("Hei maailma"))
+            }
+            event => panic!("unexpected event {:?}", event),
+        }
+        assert_eq!(events[1], ConversationEvent::Other);
+        match &events[2] {
+            ConversationEvent::MessageOutputDelta { content } => assert_eq!(content.text(), None),
+            event => panic!("unexpected event {:?}", event),
+        }
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn history_keeps_the_most_recent_conversation_first() {
+        let mut history = Vec::new();
+        record_conversation(&mut history, "conv_1", Some("ag_1"), "Moi", 100);
+        record_conversation(&mut history, "conv_2", Some("ag_2"), "Hei", 200);
+        record_conversation(&mut history, "conv_1", None, "ignored", 300);
+
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].conversation_id, "conv_1");
+        assert_eq!(history[0].title, "Moi");
+        assert_eq!(history[0].created_at, 100);
+        assert_eq!(
+            resolve_conversation_id(&history, LAST_CONVERSATION),
+            Ok("conv_1".to_string())
+        );
+        assert_eq!(
+            resolve_conversation_id(&history, "conv_9"),
+            Ok("conv_9".to_string())
+        );
+        assert!(resolve_conversation_id(&[], LAST_CONVERSATION).is_err());
+        assert_eq!(
+            conversation_title(&format!("{}\nsecond line", "a".repeat(70))),
+            format!("{}…", "a".repeat(60))
+        );
+    }
+}
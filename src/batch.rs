@@ -0,0 +1,320 @@
+use crate::{
+    client,
+    logger::Logger,
+    models::{ChatCompletionRequest, ChatMessage, UsageInfo},
+    moderation::{self, GuardMode, ModerationGuard},
+};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, path::Path, time::Duration};
+use tokio::{
+    io::AsyncWriteExt,
+    sync::Mutex,
+    time::{Interval, MissedTickBehavior},
+};
+
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
+/// One line of the input file, either a `prompt` or a full `messages` list.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BatchRequest {
+    pub id: Option<String>,
+    pub prompt: Option<String>,
+    pub messages: Option<Vec<ChatMessage>>,
+    pub model: Option<String>,
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    pub max_tokens: Option<u32>,
+}
+
+/// One line of the output file, `error` is set instead of `content` for failed requests.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct BatchResult {
+    pub id: String,
+    pub model: String,
+    pub content: Option<String>,
+    pub finish_reason: Option<String>,
+    pub usage: Option<UsageInfo>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BatchSummary {
+    pub succeeded: usize,
+    pub failed: usize,
+    pub skipped: usize,
+}
+
+impl BatchRequest {
+    pub fn to_chat_request(&self, default_model: &str) -> Result<ChatCompletionRequest, String> {
+        let messages = match (&self.prompt, &self.messages) {
+            (Some(prompt), None) => vec![ChatMessage::user(prompt.clone())],
+            (None, Some(messages)) if !messages.is_empty() => messages.clone(),
+            (Some(_), Some(_)) => return Err("Give either prompt or messages, not both".into()),
+            _ => return Err("Missing prompt or messages".into()),
+        };
+
+        let model = self
+            .model
+            .clone()
+            .unwrap_or_else(|| default_model.to_string());
+        Ok(ChatCompletionRequest {
+            stream: false,
+            temperature: self.temperature,
+            top_p: self.top_p,
+            max_tokens: self.max_tokens,
+            ..ChatCompletionRequest::new(model, messages)
+        })
+    }
+}
+
+/// Parses the input lines, requests without an id are named after their line number.
+pub fn parse_batch_input(input: &str) -> Result<Vec<(String, BatchRequest)>, String> {
+    let mut requests = Vec::new();
+    let mut ids = HashSet::new();
+
+    for (index, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let request = serde_json::from_str::<BatchRequest>(line)
+            .map_err(|e| format!("Line {}: {}", index + 1, e))?;
+        let id = request
+            .id
+            .clone()
+            .unwrap_or_else(|| format!("line-{}", index + 1));
+        if !ids.insert(id.clone()) {
+            return Err(format!("Line {}: duplicate id {}", index + 1, id));
+        }
+
+        requests.push((id, request));
+    }
+
+    Ok(requests)
+}
+
+/// Ids with a successful result in an earlier output, a truncated last line is ignored.
+pub fn completed_ids(output: &str) -> HashSet<String> {
+    output
+        .lines()
+        .filter_map(|line| serde_json::from_str::<BatchResult>(line).ok())
+        .filter(|result| result.error.is_none())
+        .map(|result| result.id)
+        .collect()
+}
+
+// waits for the next slot of `--requests-per-minute`, every API call takes one
+async fn wait_for_slot(limiter: Option<&Mutex<Interval>>) {
+    if let Some(limiter) = limiter {
+        limiter.lock().await.tick().await;
+    }
+}
+
+async fn run_request(
+    id: String,
+    request: BatchRequest,
+    default_model: &str,
+    guard: &ModerationGuard,
+    limiter: Option<&Mutex<Interval>>,
+    logger: &Logger,
+) -> BatchResult {
+    let mut result = BatchResult {
+        id,
+        model: request
+            .model
+            .clone()
+            .unwrap_or_else(|| default_model.to_string()),
+        content: None,
+        finish_reason: None,
+        usage: None,
+        error: None,
+    };
+
+    let chat_request = match request.to_chat_request(default_model) {
+        Ok(chat_request) => chat_request,
+        Err(e) => {
+            result.error = Some(e);
+            return result;
+        }
+    };
+
+    let prompt = chat_request
+        .messages
+        .iter()
+        .filter(|message| message.role == "user")
+        .map(|message| message.content.text())
+        .collect::<Vec<String>>()
+        .join("\n");
+    if guard.mode != GuardMode::Off {
+        wait_for_slot(limiter).await;
+        if let Err(e) = moderation::guard_prompt_with(guard, &prompt, logger).await {
+            result.error = Some(e);
+            return result;
+        }
+    }
+
+    wait_for_slot(limiter).await;
+
+    match client::create_mistral_completion(&chat_request, logger).await {
+        Ok(response) => {
+            let choice = response.choices.into_iter().next();
+            result.model = response.model;
+            result.usage = response.usage;
+            result.finish_reason = choice
+                .as_ref()
+                .and_then(|choice| choice.finish_reason.clone());
+            result.content = Some(
+                choice
+                    .and_then(|choice| choice.message.content)
+                    .unwrap_or_default(),
+            );
+        }
+        Err(e) => result.error = Some(e.to_string()),
+    }
+
+    result
+}
+
+/// Runs every request of `input` not yet completed in `output`, appending one result line
+/// per request as soon as it finishes so an interrupted run can be resumed.
+pub async fn run_batch(
+    input: &Path,
+    output: &Path,
+    default_model: &str,
+    concurrency: usize,
+    requests_per_minute: Option<u32>,
+    logger: &Logger,
+) -> Result<BatchSummary, Box<dyn std::error::Error>> {
+    let requests = parse_batch_input(&tokio::fs::read_to_string(input).await?)?;
+    let guard = ModerationGuard::from_config().await?;
+    let previous = tokio::fs::read_to_string(output).await.unwrap_or_default();
+    let completed = completed_ids(&previous);
+
+    let mut summary = BatchSummary::default();
+    let pending = requests
+        .into_iter()
+        .filter(|(id, _)| {
+            let done = completed.contains(id);
+            if done {
+                summary.skipped += 1;
+            }
+            !done
+        })
+        .collect::<Vec<(String, BatchRequest)>>();
+
+    let limiter = requests_per_minute.filter(|rpm| *rpm > 0).map(|rpm| {
+        let mut interval = tokio::time::interval(Duration::from_secs_f64(60.0 / rpm as f64));
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        Mutex::new(interval)
+    });
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(output)
+        .await?;
+    // an interrupted write leaves a partial line that must not swallow the next result
+    if !previous.is_empty() && !previous.ends_with('\n') {
+        file.write_all(b"\n").await?;
+    }
+
+    let total = pending.len();
+    let limiter = limiter.as_ref();
+    let mut results = futures::stream::iter(pending)
+        .map(|(id, request)| run_request(id, request, default_model, &guard, limiter, logger))
+        .buffer_unordered(concurrency.max(1));
+
+    while let Some(result) = results.next().await {
+        match &result.error {
+            Some(e) => {
+                summary.failed += 1;
+                logger
+                    .log_error(format!("Batch request {} failed: {}", result.id, e))
+                    .await;
+            }
+            None => summary.succeeded += 1,
+        }
+
+        let mut line = serde_json::to_string(&result)?;
+        line.push('\n');
+        file.write_all(line.as_bytes()).await?;
+        file.flush().await?;
+        eprint!(
+            "\r{}/{} done, {} failed",
+            summary.succeeded + summary.failed,
+            total,
+            summary.failed
+        );
+    }
+    if total > 0 {
+        eprintln!();
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_batch_input_names_and_validates_lines() {
+        let input = r#"{"id": "a", "prompt": "Moi"}
+
+{"messages": [{"role": "user", "content": "Hei"}], "model": "mistral-small-latest", "temperature": 0.2, "max_tokens": 50}
+"#;
+        let requests = parse_batch_input(input).unwrap();
+
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].0, "a");
+        assert_eq!(requests[1].0, "line-3");
+
+        let chat_request = requests[1].1.to_chat_request("mistral-tiny").unwrap();
+        assert_eq!(
+            serde_json::to_value(&chat_request).unwrap(),
+            serde_json::json!({
+                "model": "mistral-small-latest",
+                "messages": [{"role": "user", "content": "Hei"}],
+                "stream": false,
+                "temperature": 0.2,
+                "max_tokens": 50
+            })
+        );
+
+        assert_eq!(
+            parse_batch_input(
+                "{\"id\": \"a\", \"prompt\": \"1\"}\n{\"id\": \"a\", \"prompt\": \"2\"}"
+            ),
+            Err("Line 2: duplicate id a".to_string())
+        );
+        assert!(
+            parse_batch_input("{\"prompt\": ")
+                .unwrap_err()
+                .starts_with("Line 1:")
+        );
+    }
+
+    #[test]
+    fn requests_need_exactly_one_of_prompt_or_messages() {
+        let requests = parse_batch_input(
+            "{\"id\": \"empty\"}\n{\"id\": \"both\", \"prompt\": \"a\", \"messages\": []}",
+        )
+        .unwrap();
+
+        assert_eq!(
+            requests[0].1.to_chat_request("mistral-tiny").unwrap_err(),
+            "Missing prompt or messages"
+        );
+        assert!(requests[1].1.to_chat_request("mistral-tiny").is_err());
+    }
+
+    #[test]
+    fn completed_ids_skip_failures_and_truncated_lines() {
+        let output = r#"{"id": "a", "model": "m", "content": "ok", "finish_reason": "stop", "usage": null, "error": null}
+{"id": "b", "model": "m", "content": null, "finish_reason": null, "usage": null, "error": "Client Error: 500"}
+{"id": "c", "model": "m", "cont"#;
+
+        assert_eq!(completed_ids(output), HashSet::from(["a".to_string()]));
+    }
+}
@@ -1,100 +1,443 @@
-use crate::models::{MistralModelCard, MistralModelResponse};
-use crate::{config, logger::Logger, stream};
-use reqwest::Client;
-use std::env;
+use crate::models::{
+    Agent, BatchJob, BatchJobRequest, ChatCompletionRequest, ChatCompletionResponse,
+    ClassificationResponse, ConversationRequest, EmbeddingRequest, EmbeddingResponse,
+    FimCompletionRequest, FineTuningJob, FineTuningJobList, FineTuningJobRequest, MistralFile,
+    MistralModelCard, MistralModelResponse, ModelCapability, ModerationRequest, ModerationResponse,
+    OcrRequest, OcrResponse, TranscriptionRequest, TranscriptionResponse,
+};
+use crate::stream::{self, StreamEvent, StreamOutcome};
+use crate::{file, logger::Logger};
+use reqwest::{
+    Client, Method, RequestBuilder, Response, StatusCode,
+    header::HeaderMap,
+    multipart::{Form, Part},
+};
+use std::{
+    env,
+    time::{Duration, Instant},
+};
 use tokio::sync::mpsc;
 
-const API_URL: &'static str = "https://api.mistral.ai/v1/chat/completions";
-pub const DEFAULT_API_MODEL: &'static str = "mistral-tiny";
+const API_BASE_URL: &str = "https://api.mistral.ai/v1";
+pub const DEFAULT_API_MODEL: &str = "mistral-tiny";
+pub const DEFAULT_EMBEDDING_MODEL: &str = "mistral-embed";
 
-pub async fn list_mistral_models(
-    logger: &Logger,
-) -> Result<Vec<MistralModelCard>, Box<dyn std::error::Error>> {
-    let mistral_api_key = env::var("MISTRAL_API_KEY").expect("MISTRAL_API_KEY not set");
-    let client = Client::new();
+const MAX_RETRIES: u32 = 3;
+const RETRY_BASE_DELAY_MS: u64 = 500;
 
-    let response = match client
-        .get("https://api.mistral.ai/v1/models")
-        .header("Authorization", format!("Bearer {}", mistral_api_key))
-        .send()
-        .await
-    {
-        Ok(response) => {
-            if !response.status().is_success() {
+fn api_url(path: &str) -> String {
+    format!("{}{}", API_BASE_URL, path)
+}
+
+fn mistral_api_key() -> String {
+    env::var("MISTRAL_API_KEY").expect("MISTRAL_API_KEY not set")
+}
+
+fn get(path: &str) -> RequestBuilder {
+    Client::new()
+        .get(api_url(path))
+        .header("Authorization", format!("Bearer {}", mistral_api_key()))
+}
+
+fn post(path: &str) -> RequestBuilder {
+    Client::new()
+        .post(api_url(path))
+        .header("Authorization", format!("Bearer {}", mistral_api_key()))
+}
+
+// the API turned the request away without handling it
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::SERVICE_UNAVAILABLE
+}
+
+// a failed connection never reached the API, a timeout may have so only GETs repeat it
+fn is_retryable_error(e: &reqwest::Error, method: &Method) -> bool {
+    e.is_connect() || (e.is_timeout() && method == Method::GET)
+}
+
+// honours Retry-After in seconds, otherwise backs off exponentially
+fn retry_delay(headers: Option<&HeaderMap>, attempt: u32) -> Duration {
+    headers
+        .and_then(|headers| headers.get("retry-after"))
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or_else(|| Duration::from_millis(RETRY_BASE_DELAY_MS * 2u64.pow(attempt)))
+}
+
+/// Sends the request built by `build`, retrying rate limits, unavailable responses,
+/// connection failures and GET timeouts. Failures are logged and returned with the
+/// response body.
+async fn send<F>(build: F, logger: &Logger) -> Result<Response, Box<dyn std::error::Error>>
+where
+    F: Fn() -> RequestBuilder,
+{
+    let mut attempt = 0;
+
+    loop {
+        let (client, request) = build().build_split();
+        let request = request?;
+        let method = request.method().clone();
+        logger
+            .log_debug(format!("{} {}", request.method(), request.url()))
+            .await;
+
+        let started_at = Instant::now();
+        let response = match client.execute(request).await {
+            Ok(response) => response,
+            Err(e) if attempt < MAX_RETRIES && is_retryable_error(&e, &method) => {
                 logger
-                    .log_error(format!("Client Error: {}", response.status()))
+                    .log_warn(format!("Client Error: {}, retrying", e))
                     .await;
-
-                return Err(Box::new(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    format!("Client Error: {}", response.status()),
-                )));
+                tokio::time::sleep(retry_delay(None, attempt)).await;
+                attempt += 1;
+                continue;
             }
+            Err(e) => {
+                logger.log_error(format!("Client Error: {}", e)).await;
+                return Err(Box::new(e));
+            }
+        };
 
-            response
-                .json::<MistralModelResponse>()
-                .await
-                .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?
-                .data
+        let status = response.status();
+        logger
+            .log_debug(format!(
+                "{} {} after {} ms",
+                status,
+                response.url().path(),
+                started_at.elapsed().as_millis()
+            ))
+            .await;
+        if status.is_success() {
+            return Ok(response);
         }
-        Err(e) => {
-            logger.log_error(format!("Client Error: {}", e)).await;
 
-            return Err(Box::new(e));
+        if is_retryable(status) && attempt < MAX_RETRIES {
+            logger
+                .log_warn(format!("Client Error: {}, retrying", status))
+                .await;
+            tokio::time::sleep(retry_delay(Some(response.headers()), attempt)).await;
+            attempt += 1;
+            continue;
         }
-    };
 
-    // filter only models with chat completion
+        let body = response.text().await.unwrap_or_default();
+        logger
+            .log_error(format!("Client Error: {}\n{}", status, body))
+            .await;
+
+        return Err(Box::new(std::io::Error::other(format!(
+            "Client Error: {} {}",
+            status, body
+        ))));
+    }
+}
+
+/// Fetches every model card available to the API key and refreshes the local model cache.
+pub async fn fetch_mistral_models(
+    logger: &Logger,
+) -> Result<Vec<MistralModelCard>, Box<dyn std::error::Error>> {
+    let response = send(|| get("/models"), logger)
+        .await?
+        .json::<MistralModelResponse>()
+        .await
+        .map_err(|e| Box::new(e) as Box<dyn std::error::Error>)?
+        .data;
+
+    if let Err(e) = file::write_models_cache(&response).await {
+        logger
+            .log_error(format!("Failed to write model cache: {}", e))
+            .await;
+    }
+
+    Ok(response)
+}
+
+/// Lists the models supporting `capability`, e.g. chat completion for the model picker.
+pub async fn list_mistral_models(
+    logger: &Logger,
+    capability: ModelCapability,
+) -> Result<Vec<MistralModelCard>, Box<dyn std::error::Error>> {
+    let response = fetch_mistral_models(logger).await?;
 
     Ok(response
         .into_iter()
-        .filter(|model| model.capabilities.completion_chat == true)
+        .filter(|model| model.capabilities.supports(capability))
         .collect())
 }
 
 pub async fn call_mistral_completions(
-    prompt: String,
-    sender: mpsc::Sender<String>,
+    request_body: &ChatCompletionRequest,
+    sender: mpsc::Sender<StreamEvent>,
     logger: &Logger,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mistral_model = config::get_model_name()
-        .await
-        .unwrap_or_else(|_| DEFAULT_API_MODEL.to_string());
-    let mistral_api_key = env::var("MISTRAL_API_KEY").expect("MISTRAL_API_KEY not set");
-    let client = Client::new();
-
-    let request_body = serde_json::json!({
-        "model": mistral_model,
-        "messages": [{"role": "user", "content": prompt}],
-        "stream": true
-    });
-
-    let response = match client
-        .post(API_URL)
-        .header("Content-Type", "application/json")
-        .header("Authorization", format!("Bearer {}", mistral_api_key))
-        .json(&request_body)
-        .send()
-        .await
-    {
-        Ok(response) => {
-            if !response.status().is_success() {
-                logger
-                    .log_error(format!("Client Error: {}", response.status()))
-                    .await;
-            }
+) -> Result<StreamOutcome, Box<dyn std::error::Error>> {
+    let response = send(|| post("/chat/completions").json(request_body), logger).await?;
 
-            response
-        }
-        Err(e) => {
-            logger.log_error(format!("Client Error: {}", e)).await;
-            return Err(e.into());
-        }
+    let outcome =
+        stream::parse_mistral_stream_outcome(Box::pin(response.bytes_stream()), sender, logger)
+            .await
+            .expect("Result stream chunking failed");
+
+    Ok(outcome)
+}
+
+/// Sends a completion without streaming and waits for the whole answer.
+pub async fn create_mistral_completion(
+    request_body: &ChatCompletionRequest,
+    logger: &Logger,
+) -> Result<ChatCompletionResponse, Box<dyn std::error::Error>> {
+    let mut request_body = request_body.clone();
+    request_body.stream = false;
+
+    Ok(
+        send(|| post("/chat/completions").json(&request_body), logger)
+            .await?
+            .json::<ChatCompletionResponse>()
+            .await?,
+    )
+}
+
+/// Streams a fill-in-the-middle completion, the chunks share the chat completion format.
+pub async fn call_mistral_fim(
+    request_body: &FimCompletionRequest,
+    sender: mpsc::Sender<StreamEvent>,
+    logger: &Logger,
+) -> Result<StreamOutcome, Box<dyn std::error::Error>> {
+    let response = send(|| post("/fim/completions").json(request_body), logger).await?;
+
+    let outcome =
+        stream::parse_mistral_stream_outcome(Box::pin(response.bytes_stream()), sender, logger)
+            .await
+            .expect("Result stream chunking failed");
+
+    Ok(outcome)
+}
+
+pub async fn create_embeddings(
+    request_body: &EmbeddingRequest,
+    logger: &Logger,
+) -> Result<EmbeddingResponse, Box<dyn std::error::Error>> {
+    Ok(send(|| post("/embeddings").json(request_body), logger)
+        .await?
+        .json::<EmbeddingResponse>()
+        .await?)
+}
+
+pub async fn call_mistral_ocr(
+    request_body: &OcrRequest,
+    logger: &Logger,
+) -> Result<OcrResponse, Box<dyn std::error::Error>> {
+    Ok(send(|| post("/ocr").json(request_body), logger)
+        .await?
+        .json::<OcrResponse>()
+        .await?)
+}
+
+pub async fn call_mistral_moderation(
+    request_body: &ModerationRequest,
+    logger: &Logger,
+) -> Result<ModerationResponse, Box<dyn std::error::Error>> {
+    Ok(send(|| post("/moderations").json(request_body), logger)
+        .await?
+        .json::<ModerationResponse>()
+        .await?)
+}
+
+pub async fn call_mistral_classification(
+    request_body: &ModerationRequest,
+    logger: &Logger,
+) -> Result<ClassificationResponse, Box<dyn std::error::Error>> {
+    Ok(send(|| post("/classifications").json(request_body), logger)
+        .await?
+        .json::<ClassificationResponse>()
+        .await?)
+}
+
+// multipart forms can't be cloned, so every attempt builds its own from the request
+fn transcription_form(request: &TranscriptionRequest) -> Result<Form, reqwest::Error> {
+    let file = Part::bytes(request.audio.clone())
+        .file_name(request.file_name.clone())
+        .mime_str(&request.mime_type)?;
+
+    let mut form = Form::new()
+        .text("model", request.model.clone())
+        .part("file", file);
+    if let Some(language) = &request.language {
+        form = form.text("language", language.clone());
+    }
+    if request.segment_timestamps {
+        form = form.text("timestamp_granularities", "segment");
+    }
+
+    Ok(form)
+}
+
+pub async fn call_mistral_transcription(
+    request: &TranscriptionRequest,
+    logger: &Logger,
+) -> Result<TranscriptionResponse, Box<dyn std::error::Error>> {
+    // validates the mime type once, the retried builds below can't fail anymore
+    transcription_form(request)?;
+
+    Ok(send(
+        || {
+            post("/audio/transcriptions")
+                .multipart(transcription_form(request).expect("Transcription form is valid"))
+        },
+        logger,
+    )
+    .await?
+    .json::<TranscriptionResponse>()
+    .await?)
+}
+
+/// Uploads `contents` as a file for `purpose`, e.g. `batch` or `fine-tune`.
+pub async fn upload_file(
+    file_name: &str,
+    contents: &[u8],
+    purpose: &str,
+    logger: &Logger,
+) -> Result<MistralFile, Box<dyn std::error::Error>> {
+    let form = || {
+        Form::new().text("purpose", purpose.to_string()).part(
+            "file",
+            Part::bytes(contents.to_vec()).file_name(file_name.to_string()),
+        )
     };
 
-    stream::parse_mistral_stream(Box::pin(response.bytes_stream()), sender, &logger)
-        .await
-        .expect("Result stream chunking failed");
+    Ok(send(|| post("/files").multipart(form()), logger)
+        .await?
+        .json::<MistralFile>()
+        .await?)
+}
+
+pub async fn download_file(
+    file_id: &str,
+    logger: &Logger,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let path = format!("/files/{}/content", file_id);
+    Ok(send(|| get(&path), logger).await?.bytes().await?.to_vec())
+}
+
+pub async fn create_batch_job(
+    request_body: &BatchJobRequest,
+    logger: &Logger,
+) -> Result<BatchJob, Box<dyn std::error::Error>> {
+    Ok(send(|| post("/batch/jobs").json(request_body), logger)
+        .await?
+        .json::<BatchJob>()
+        .await?)
+}
+
+pub async fn get_batch_job(
+    job_id: &str,
+    logger: &Logger,
+) -> Result<BatchJob, Box<dyn std::error::Error>> {
+    let path = format!("/batch/jobs/{}", job_id);
+    Ok(send(|| get(&path), logger)
+        .await?
+        .json::<BatchJob>()
+        .await?)
+}
+
+pub async fn cancel_batch_job(
+    job_id: &str,
+    logger: &Logger,
+) -> Result<BatchJob, Box<dyn std::error::Error>> {
+    let path = format!("/batch/jobs/{}/cancel", job_id);
+    Ok(send(|| post(&path), logger)
+        .await?
+        .json::<BatchJob>()
+        .await?)
+}
+
+pub async fn create_fine_tuning_job(
+    request_body: &FineTuningJobRequest,
+    logger: &Logger,
+) -> Result<FineTuningJob, Box<dyn std::error::Error>> {
+    Ok(
+        send(|| post("/fine_tuning/jobs").json(request_body), logger)
+            .await?
+            .json::<FineTuningJob>()
+            .await?,
+    )
+}
+
+pub async fn list_fine_tuning_jobs(
+    logger: &Logger,
+) -> Result<FineTuningJobList, Box<dyn std::error::Error>> {
+    Ok(send(|| get("/fine_tuning/jobs?page_size=100"), logger)
+        .await?
+        .json::<FineTuningJobList>()
+        .await?)
+}
+
+pub async fn get_fine_tuning_job(
+    job_id: &str,
+    logger: &Logger,
+) -> Result<FineTuningJob, Box<dyn std::error::Error>> {
+    let path = format!("/fine_tuning/jobs/{}", job_id);
+    Ok(send(|| get(&path), logger)
+        .await?
+        .json::<FineTuningJob>()
+        .await?)
+}
+
+pub async fn cancel_fine_tuning_job(
+    job_id: &str,
+    logger: &Logger,
+) -> Result<FineTuningJob, Box<dyn std::error::Error>> {
+    let path = format!("/fine_tuning/jobs/{}/cancel", job_id);
+    Ok(send(|| post(&path), logger)
+        .await?
+        .json::<FineTuningJob>()
+        .await?)
+}
+
+pub async fn list_agents(logger: &Logger) -> Result<Vec<Agent>, Box<dyn std::error::Error>> {
+    Ok(send(|| get("/agents?page_size=100"), logger)
+        .await?
+        .json::<Vec<Agent>>()
+        .await?)
+}
+
+/// Starts a conversation, or appends to `conversation_id`, returning the event stream.
+pub async fn call_mistral_conversation(
+    request_body: &ConversationRequest,
+    conversation_id: Option<&str>,
+    logger: &Logger,
+) -> Result<Response, Box<dyn std::error::Error>> {
+    let path = match conversation_id {
+        Some(conversation_id) => format!("/conversations/{}", conversation_id),
+        None => "/conversations".to_string(),
+    };
+    send(|| post(&path).json(request_body), logger).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_turned_away_requests_are_retried() {
+        assert!(is_retryable(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_retryable(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!is_retryable(StatusCode::INTERNAL_SERVER_ERROR));
+        assert!(!is_retryable(StatusCode::GATEWAY_TIMEOUT));
+        assert!(!is_retryable(StatusCode::BAD_REQUEST));
+    }
+
+    #[test]
+    fn retry_delay_prefers_retry_after() {
+        let mut headers = HeaderMap::new();
+        headers.insert("retry-after", "7".parse().unwrap());
 
-    Ok(())
+        assert_eq!(retry_delay(Some(&headers), 0), Duration::from_secs(7));
+        assert_eq!(retry_delay(None, 0), Duration::from_millis(500));
+        assert_eq!(retry_delay(None, 2), Duration::from_millis(2000));
+        assert_eq!(
+            retry_delay(Some(&HeaderMap::new()), 1),
+            Duration::from_millis(1000)
+        );
+    }
 }
@@ -0,0 +1,143 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use std::{
+    env,
+    io::{self, IsTerminal, Write},
+    process::{Command, Stdio},
+    thread,
+};
+
+// tried in order when OSC 52 can't be used
+const CLIPBOARD_COMMANDS: [(&str, &[&str]); 2] =
+    [("wl-copy", &[]), ("xclip", &["-selection", "clipboard"])];
+
+/// A fenced code block of a response, `start` being the byte offset of its opening fence.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeBlock {
+    pub language: Option<String>,
+    pub code: String,
+    pub start: usize,
+}
+
+/// The ```` ``` ```` fenced blocks of `text`. A block still streaming runs to the end.
+pub fn code_blocks(text: &str) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    let mut open: Option<CodeBlock> = None;
+
+    for line in text.lines() {
+        let fence = line.trim_start().strip_prefix("```");
+        match (open.take(), fence) {
+            (None, Some(language)) => {
+                open = Some(CodeBlock {
+                    language: Some(language.trim().to_string()).filter(|l| !l.is_empty()),
+                    code: String::new(),
+                    start: line.as_ptr() as usize - text.as_ptr() as usize,
+                });
+            }
+            (Some(block), Some(rest)) if rest.trim().is_empty() => blocks.push(block),
+            (Some(mut block), _) => {
+                block.code.push_str(line);
+                block.code.push('\n');
+                open = Some(block);
+            }
+            (None, None) => {}
+        }
+    }
+    blocks.extend(open);
+
+    blocks
+}
+
+/// The OSC 52 sequence setting the clipboard to `text`, wrapped for tmux to pass it on.
+pub fn osc52_sequence(text: &str, tmux: bool) -> String {
+    let sequence = format!("\x1b]52;c;{}\x07", STANDARD.encode(text));
+    if tmux {
+        format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+    } else {
+        sequence
+    }
+}
+
+/// OSC 52 needs a terminal on stdout that understands escape sequences.
+fn osc52_usable(is_terminal: bool, term: Option<&str>) -> bool {
+    is_terminal && !matches!(term.map(str::trim), None | Some("") | Some("dumb"))
+}
+
+// the text is written and the tool reaped on a thread of its own, so a clipboard tool
+// that keeps running doesn't stall the UI
+fn pipe_to(program: &str, args: &[&str], text: &str) -> io::Result<()> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let stdin = child.stdin.take();
+    let text = text.to_string();
+    thread::spawn(move || {
+        if let Some(mut stdin) = stdin {
+            _ = stdin.write_all(text.as_bytes());
+        }
+        _ = child.wait();
+    });
+    Ok(())
+}
+
+/// Copies `text` with OSC 52, which also reaches the local clipboard over SSH, or with
+/// wl-copy or xclip when stdout is not a terminal that takes escape sequences. Returns
+/// the way it was copied.
+pub fn copy(text: &str) -> Option<&'static str> {
+    let term = env::var("TERM").ok();
+    if osc52_usable(io::stdout().is_terminal(), term.as_deref()) {
+        let sequence = osc52_sequence(text, env::var_os("TMUX").is_some());
+        let mut stdout = io::stdout();
+        if stdout
+            .write_all(sequence.as_bytes())
+            .and_then(|_| stdout.flush())
+            .is_ok()
+        {
+            return Some("OSC 52");
+        }
+    }
+
+    CLIPBOARD_COMMANDS
+        .iter()
+        .find(|(program, args)| pipe_to(program, args, text).is_ok())
+        .map(|(program, _)| *program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fenced_blocks_are_found() {
+        let text = "Run this:\n```rust\nfn main() {}\n```\nthen\n  ```\nls -la\n\n```\n```sh\necho streaming";
+        let blocks = code_blocks(text);
+
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[0].language.as_deref(), Some("rust"));
+        assert_eq!(blocks[0].code, "fn main() {}\n");
+        assert_eq!(blocks[0].start, 10);
+        assert_eq!(blocks[1].language, None);
+        assert_eq!(blocks[1].code, "ls -la\n\n");
+        assert_eq!(blocks[2].code, "echo streaming\n");
+        assert!(code_blocks("no code").is_empty());
+    }
+
+    #[test]
+    fn osc52_is_wrapped_for_tmux() {
+        assert_eq!(osc52_sequence("hi", false), "\x1b]52;c;aGk=\x07");
+        assert_eq!(
+            osc52_sequence("hi", true),
+            "\x1bPtmux;\x1b\x1b]52;c;aGk=\x07\x1b\\"
+        );
+    }
+
+    #[test]
+    fn osc52_needs_a_capable_terminal() {
+        assert!(osc52_usable(true, Some("xterm-256color")));
+        assert!(!osc52_usable(true, Some("dumb")));
+        assert!(!osc52_usable(true, None));
+        assert!(!osc52_usable(false, Some("xterm-256color")));
+    }
+}
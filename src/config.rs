@@ -2,32 +2,90 @@ use crate::client::DEFAULT_API_MODEL;
 use crate::file;
 use tokio::{fs::read_to_string, io::AsyncWriteExt};
 
-pub async fn get_model_name() -> Result<String, Box<dyn std::error::Error>> {
+const MODEL_CONFIG_KEY: &str = "MISTRAL_MODEL";
+
+// entries: KEY="value", keys may repeat for list settings
+fn parse_config_line(line: &str) -> Option<(&str, String)> {
+    let (key, value) = line.split_once('=')?;
+    let value = value.trim();
+    let value = value
+        .strip_prefix('"')
+        .and_then(|value| value.strip_suffix('"'))
+        .unwrap_or(value);
+    Some((key.trim(), value.to_string()))
+}
+
+/// Returns every value stored for `key`, in file order.
+pub async fn get_config_values(key: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
     let contents = match read_to_string(file::get_config_file_path()).await {
         Ok(text) => text,
         Err(e) => return Err(e.into()),
     };
 
-    let model_config = contents
+    Ok(contents
         .lines()
-        .filter(|line| line.starts_with("MISTRAL_MODEL"))
-        .map(|line| line.split('=').collect::<Vec<&str>>())
-        .map(|line| line[1].to_string())
+        .filter_map(parse_config_line)
+        .filter(|(line_key, _)| *line_key == key)
+        .map(|(_, value)| value)
+        .collect())
+}
+
+/// Returns the first value stored for `key`, `None` when missing or unreadable.
+pub async fn get_config_value(key: &str) -> Option<String> {
+    get_config_values(key).await.ok()?.into_iter().next()
+}
+
+/// Replaces all values of `key` with `value`, keeping the other settings intact.
+pub async fn set_config_value(key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let contents = read_to_string(file::get_config_file_path())
+        .await
+        .unwrap_or_default();
+
+    let mut lines = contents
+        .lines()
+        .filter(|line| !matches!(parse_config_line(line), Some((line_key, _)) if line_key == key))
+        .map(str::to_string)
         .collect::<Vec<String>>();
+    lines.push(format!("{}=\"{}\"", key, value));
+
+    let (_, mut file) = file::create_config_file(Some(true)).await;
+    file.write_all(lines.join("\n").as_bytes()).await?;
+
+    Ok(())
+}
+
+pub async fn get_model_name() -> Result<String, Box<dyn std::error::Error>> {
+    let model_config = get_config_values(MODEL_CONFIG_KEY).await?;
 
-    if model_config.len() == 0 {
+    if model_config.is_empty() {
         return Ok(DEFAULT_API_MODEL.to_string());
     }
 
-    let model_name = model_config[0].clone();
-    let stripped_model_name = model_name.replace("\"", "");
-    Ok(stripped_model_name)
+    Ok(model_config[0].clone())
 }
 
 pub async fn save_model_name(model_name: String) -> Result<(), Box<dyn std::error::Error>> {
-    let (_, mut file) = file::create_config_file(Some(true)).await;
-    file.write_all(format!("MISTRAL_MODEL=\"{}\"", model_name).as_bytes())
-        .await?;
+    set_config_value(MODEL_CONFIG_KEY, &model_name).await
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_surrounding_quotes_are_stripped() {
+        assert_eq!(
+            parse_config_line(r#"SYSTEM_PROMPT="Say "hi" back""#),
+            Some(("SYSTEM_PROMPT", r#"Say "hi" back"#.to_string()))
+        );
+        assert_eq!(
+            parse_config_line(" MISTRAL_MODEL = mistral-small "),
+            Some(("MISTRAL_MODEL", "mistral-small".to_string()))
+        );
+        assert_eq!(
+            parse_config_line(r#"KEY=""#),
+            Some(("KEY", "\"".to_string()))
+        );
+        assert_eq!(parse_config_line("no entry"), None);
+    }
 }
@@ -0,0 +1,289 @@
+use crate::{client, logger::Logger, models::EmbeddingRequest};
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+use tokio::io::AsyncReadExt;
+
+/// Header of the binary matrix format: magic, then rows and dimensions as little-endian
+/// u32, then every row as little-endian f32 values.
+pub const MATRIX_MAGIC: &[u8; 8] = b"JWEKEMB1";
+
+// keeps a single request well below the endpoint's token limit
+const MAX_BATCH_INPUTS: usize = 64;
+const MAX_BATCH_CHARS: usize = 24_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum EmbeddingFormat {
+    /// One JSON object per input with its source and vector
+    Jsonl,
+    /// Row-major f32 matrix with a small header
+    Bin,
+}
+
+/// Text to embed together with where it came from, a file path or `text:<n>`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmbeddingInput {
+    pub source: String,
+    pub text: String,
+}
+
+/// Collects the texts given as arguments and the contents of `files`, stdin is read when
+/// neither is given.
+pub async fn read_embedding_inputs(
+    texts: &[String],
+    files: &[PathBuf],
+) -> Result<Vec<EmbeddingInput>, Box<dyn std::error::Error>> {
+    let mut inputs = texts
+        .iter()
+        .enumerate()
+        .map(|(index, text)| EmbeddingInput {
+            source: format!("text:{}", index),
+            text: text.clone(),
+        })
+        .collect::<Vec<EmbeddingInput>>();
+
+    for path in files {
+        let text = tokio::fs::read_to_string(path)
+            .await
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        inputs.push(EmbeddingInput {
+            source: path.display().to_string(),
+            text,
+        });
+    }
+
+    if inputs.is_empty() {
+        let mut text = String::new();
+        tokio::io::stdin().read_to_string(&mut text).await?;
+        inputs.push(EmbeddingInput {
+            source: "stdin".to_string(),
+            text,
+        });
+    }
+
+    if let Some(empty) = inputs.iter().find(|input| input.text.trim().is_empty()) {
+        return Err(format!("Nothing to embed in {}", empty.source).into());
+    }
+
+    Ok(inputs)
+}
+
+// an input over the batch budget would get a request of its own and still be too long
+fn truncate_input(text: &str) -> &str {
+    let mut end = text.len().min(MAX_BATCH_CHARS);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+fn batch_ranges(texts: &[String]) -> Vec<std::ops::Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    let mut chars = 0;
+
+    for (index, text) in texts.iter().enumerate() {
+        let full = index - start >= MAX_BATCH_INPUTS || chars + text.len() > MAX_BATCH_CHARS;
+        if full && index > start {
+            ranges.push(start..index);
+            start = index;
+            chars = 0;
+        }
+        chars += text.len();
+    }
+
+    if start < texts.len() {
+        ranges.push(start..texts.len());
+    }
+
+    ranges
+}
+
+/// Embeds `texts` in as few requests as the batch limits allow, keeping the input order.
+pub async fn embed_texts(
+    texts: &[String],
+    model: &str,
+    logger: &Logger,
+    mut on_progress: impl FnMut(usize, usize),
+) -> Result<Vec<Vec<f32>>, Box<dyn std::error::Error>> {
+    let mut vectors = Vec::with_capacity(texts.len());
+
+    let mut inputs = Vec::with_capacity(texts.len());
+    for (index, text) in texts.iter().enumerate() {
+        let input = truncate_input(text);
+        if input.len() < text.len() {
+            logger
+                .log_warn(format!(
+                    "Input {} has {} bytes, only the first {} are embedded",
+                    index,
+                    text.len(),
+                    input.len()
+                ))
+                .await;
+        }
+        inputs.push(input.to_string());
+    }
+    let texts = &inputs;
+
+    for range in batch_ranges(texts) {
+        let request = EmbeddingRequest {
+            model: model.to_string(),
+            input: texts[range.clone()].to_vec(),
+        };
+        let mut data = client::create_embeddings(&request, logger).await?.data;
+        if data.len() != range.len() {
+            return Err(format!(
+                "Expected {} embeddings, received {}",
+                range.len(),
+                data.len()
+            )
+            .into());
+        }
+
+        data.sort_by_key(|embedding| embedding.index);
+        vectors.extend(data.into_iter().map(|embedding| embedding.embedding));
+        on_progress(vectors.len(), texts.len());
+    }
+
+    Ok(vectors)
+}
+
+pub fn write_matrix(writer: &mut impl Write, vectors: &[Vec<f32>]) -> io::Result<()> {
+    let dimensions = vectors.first().map(|vector| vector.len()).unwrap_or(0);
+
+    writer.write_all(MATRIX_MAGIC)?;
+    writer.write_all(&(vectors.len() as u32).to_le_bytes())?;
+    writer.write_all(&(dimensions as u32).to_le_bytes())?;
+    for vector in vectors {
+        if vector.len() != dimensions {
+            return Err(io::Error::other("Embedding dimensions differ between rows"));
+        }
+        for value in vector {
+            writer.write_all(&value.to_le_bytes())?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn read_matrix(bytes: &[u8]) -> Result<Vec<Vec<f32>>, String> {
+    let header = bytes
+        .get(..16)
+        .ok_or_else(|| "Embedding matrix is truncated".to_string())?;
+    if &header[..8] != MATRIX_MAGIC {
+        return Err("Not an embedding matrix".to_string());
+    }
+
+    let rows = u32::from_le_bytes(header[8..12].try_into().unwrap()) as usize;
+    let dimensions = u32::from_le_bytes(header[12..16].try_into().unwrap()) as usize;
+    let values = &bytes[16..];
+    if values.len() != rows * dimensions * 4 {
+        return Err("Embedding matrix is truncated".to_string());
+    }
+
+    Ok(values
+        .chunks_exact(4)
+        .map(|value| f32::from_le_bytes(value.try_into().unwrap()))
+        .collect::<Vec<f32>>()
+        .chunks(dimensions.max(1))
+        .take(rows)
+        .map(|row| row.to_vec())
+        .collect())
+}
+
+pub fn to_jsonl(inputs: &[EmbeddingInput], vectors: &[Vec<f32>]) -> String {
+    inputs
+        .iter()
+        .zip(vectors)
+        .enumerate()
+        .map(|(index, (input, vector))| {
+            serde_json::json!({
+                "index": index,
+                "source": input.source,
+                "embedding": vector,
+            })
+            .to_string()
+                + "\n"
+        })
+        .collect()
+}
+
+/// Writes the vectors to `output`, or stdout when no file is given.
+pub fn write_embeddings(
+    output: Option<&Path>,
+    format: EmbeddingFormat,
+    inputs: &[EmbeddingInput],
+    vectors: &[Vec<f32>],
+) -> io::Result<()> {
+    let mut writer: Box<dyn Write> = match output {
+        Some(path) => Box::new(io::BufWriter::new(std::fs::File::create(path)?)),
+        None => Box::new(io::stdout().lock()),
+    };
+
+    match format {
+        EmbeddingFormat::Jsonl => writer.write_all(to_jsonl(inputs, vectors).as_bytes())?,
+        EmbeddingFormat::Bin => write_matrix(&mut writer, vectors)?,
+    }
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matrix_round_trip() {
+        let vectors = vec![vec![0.5, -1.0, 2.25], vec![0.0, 1.0, -0.125]];
+        let mut bytes = Vec::new();
+        write_matrix(&mut bytes, &vectors).unwrap();
+
+        assert_eq!(&bytes[..8], MATRIX_MAGIC);
+        assert_eq!(bytes.len(), 16 + 2 * 3 * 4);
+        assert_eq!(read_matrix(&bytes), Ok(vectors));
+        assert!(read_matrix(&bytes[..20]).is_err());
+    }
+
+    #[test]
+    fn batch_ranges_respect_input_and_char_limits() {
+        let small = vec!["a".to_string(); MAX_BATCH_INPUTS + 1];
+        assert_eq!(
+            batch_ranges(&small),
+            vec![0..MAX_BATCH_INPUTS, MAX_BATCH_INPUTS..MAX_BATCH_INPUTS + 1]
+        );
+
+        let large = vec!["x".repeat(MAX_BATCH_CHARS / 2 + 1); 3];
+        assert_eq!(batch_ranges(&large), vec![0..1, 1..2, 2..3]);
+        assert!(batch_ranges(&[]).is_empty());
+    }
+
+    #[test]
+    fn oversized_inputs_are_truncated_on_a_char_boundary() {
+        let text = format!("{}ä", "x".repeat(MAX_BATCH_CHARS - 1));
+        assert_eq!(truncate_input(&text).len(), MAX_BATCH_CHARS - 1);
+        assert_eq!(truncate_input("moi"), "moi");
+    }
+
+    #[test]
+    fn jsonl_lines_keep_sources() {
+        let inputs = vec![
+            EmbeddingInput {
+                source: "text:0".to_string(),
+                text: "moi".to_string(),
+            },
+            EmbeddingInput {
+                source: "src/main.rs".to_string(),
+                text: "fn main() {}".to_string(),
+            },
+        ];
+        let jsonl = to_jsonl(&inputs, &[vec![1.0], vec![0.5]]);
+        let lines = jsonl.lines().collect::<Vec<&str>>();
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(lines[1]).unwrap(),
+            serde_json::json!({"index": 1, "source": "src/main.rs", "embedding": [0.5]})
+        );
+    }
+}
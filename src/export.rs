@@ -0,0 +1,84 @@
+use crate::ui::StreamPane;
+use std::{path::Path, time::Duration};
+
+fn format_latency(latency: Option<Duration>) -> String {
+    match latency {
+        Some(latency) if latency.as_millis() < 1000 => format!("{} ms", latency.as_millis()),
+        Some(latency) => format!("{:.2} s", latency.as_secs_f64()),
+        None => "-".to_string(),
+    }
+}
+
+/// Renders a comparison run as markdown: a summary table followed by one section per model.
+pub fn compare_to_markdown(prompt: &str, panes: &[StreamPane]) -> String {
+    let mut markdown = String::new();
+    markdown.push_str("# Model comparison\n\n");
+    markdown.push_str(&format!("**Prompt:** {}\n\n", prompt));
+    markdown.push_str("| Model | First token | Total | Prompt tokens | Completion tokens |\n");
+    markdown.push_str("| --- | --- | --- | --- | --- |\n");
+
+    for pane in panes {
+        let prompt_tokens = pane
+            .usage
+            .as_ref()
+            .map(|usage| usage.prompt_tokens.to_string())
+            .unwrap_or_else(|| "-".to_string());
+        markdown.push_str(&format!(
+            "| {} | {} | {} | {} | {} |\n",
+            pane.model,
+            format_latency(pane.first_token_latency()),
+            format_latency(pane.total_latency()),
+            prompt_tokens,
+            pane.completion_tokens()
+        ));
+    }
+
+    for pane in panes {
+        markdown.push_str(&format!("\n## {}\n\n", pane.model));
+        markdown.push_str(pane.content.trim_end());
+        markdown.push('\n');
+    }
+
+    markdown
+}
+
+pub async fn write_markdown_export(
+    path: &Path,
+    prompt: &str,
+    panes: &[StreamPane],
+) -> Result<(), Box<dyn std::error::Error>> {
+    tokio::fs::write(path, compare_to_markdown(prompt, panes)).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{models::UsageInfo, stream::StreamEvent};
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn compare_to_markdown_lists_every_model() {
+        let (_, receiver_a) = mpsc::channel(1);
+        let (_, receiver_b) = mpsc::channel(1);
+
+        let mut pane_a = StreamPane::new("mistral-tiny".to_string(), receiver_a);
+        pane_a.apply_event(StreamEvent::Token("Turku".to_string()));
+        pane_a.apply_event(StreamEvent::Usage(UsageInfo {
+            prompt_tokens: 7,
+            completion_tokens: 3,
+            total_tokens: 10,
+        }));
+        let mut pane_b = StreamPane::new("mistral-medium".to_string(), receiver_b);
+        pane_b.apply_event(StreamEvent::Token("Helsinki".to_string()));
+
+        let markdown = compare_to_markdown("Capital?", &[pane_a, pane_b]);
+
+        assert!(markdown.contains("**Prompt:** Capital?"));
+        assert!(markdown.contains("| mistral-tiny |"));
+        assert!(markdown.contains("| 7 | 3 |"));
+        assert!(markdown.contains("| - | 1 |"));
+        assert!(markdown.contains("## mistral-tiny\n\nTurku\n"));
+        assert!(markdown.contains("## mistral-medium\n\nHelsinki\n"));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::models::MistralModelCard;
 use std::path::PathBuf;
 use tokio::fs::{File, OpenOptions};
 
@@ -5,6 +6,9 @@ const CLI_DIR: &str = ".config/jwekke-ai-cli";
 const CONFIG_FILE_NAME: &str = "config.jwek";
 const STREAM_LOG_FILE_PATH: &str = "stream.log";
 const ERROR_LOG_FILE_PATH: &str = "error.log";
+const MODELS_CACHE_FILE_PATH: &str = "models.cache";
+const INDEXES_DIR: &str = "indexes";
+const CONVERSATIONS_FILE_PATH: &str = "conversations.json";
 
 pub fn get_cli_config_dir() -> PathBuf {
     let home_dir = dirs::home_dir().expect("Failed to get home directory");
@@ -65,6 +69,47 @@ pub fn get_stream_log_file_path() -> PathBuf {
     dir_path.join(STREAM_LOG_FILE_PATH)
 }
 
+pub fn get_models_cache_file_path() -> PathBuf {
+    let dir_path = get_cli_config_dir();
+    dir_path.join(MODELS_CACHE_FILE_PATH)
+}
+
+pub fn get_conversations_file_path() -> PathBuf {
+    let dir_path = get_cli_config_dir();
+    dir_path.join(CONVERSATIONS_FILE_PATH)
+}
+
+pub fn get_indexes_dir() -> PathBuf {
+    let dir_path = get_cli_config_dir();
+    dir_path.join(INDEXES_DIR)
+}
+
+/// The directory of index `name`, which must be a plain directory name.
+pub fn get_index_dir(name: &str) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    if name.is_empty() || name == "." || name.contains("..") || name.contains(['/', '\\']) {
+        return Err(format!(
+            "Invalid index name \"{}\", path separators and .. are not allowed",
+            name
+        )
+        .into());
+    }
+    Ok(get_indexes_dir().join(name))
+}
+
+/// Reads the model cards stored by the last successful `/v1/models` call.
+pub async fn read_models_cache() -> Result<Vec<MistralModelCard>, Box<dyn std::error::Error>> {
+    let contents = tokio::fs::read(get_models_cache_file_path()).await?;
+    Ok(serde_json::from_slice(&contents)?)
+}
+
+pub async fn write_models_cache(
+    models: &[MistralModelCard],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let contents = serde_json::to_vec(models)?;
+    tokio::fs::write(get_models_cache_file_path(), contents).await?;
+    Ok(())
+}
+
 pub async fn open_config_file() -> (PathBuf, File) {
     let config_file_path = get_config_file_path();
 
@@ -76,25 +121,9 @@ pub async fn open_config_file() -> (PathBuf, File) {
     }
 }
 
-pub async fn create_stream_log_file() -> (PathBuf, File) {
-    let stream_log_file_path = get_stream_log_file_path();
-    match create_file(stream_log_file_path, true).await {
-        Ok(file) => file,
-        Err(e) => panic!("Failed to create log file: {}", e),
-    }
-}
-
-pub async fn create_error_log_file() -> (PathBuf, File) {
-    let error_log_file_path = get_error_log_file_path();
-    match create_file(error_log_file_path, true).await {
-        Ok(file) => file,
-        Err(e) => panic!("Failed to create log file: {}", e),
-    }
-}
-
 pub async fn create_config_file(truncate: Option<bool>) -> (PathBuf, File) {
     let config_file_path = get_config_file_path();
-    match create_file(config_file_path, truncate.unwrap_or_else(|| true)).await {
+    match create_file(config_file_path, truncate.unwrap_or(true)).await {
         Ok(file) => file,
         Err(e) => panic!("Failed to create config file: {}", e),
     }
@@ -0,0 +1,118 @@
+use crate::{client, logger::Logger, models::FimCompletionRequest, stream::StreamEvent};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+};
+use tokio::io::AsyncReadExt;
+
+pub const DEFAULT_FIM_MODEL: &str = "codestral-latest";
+pub const DEFAULT_CURSOR_MARKER: &str = "<CURSOR>";
+
+/// Splits editor input at the first cursor marker, input without a marker is all prefix.
+pub fn split_at_marker(input: &str, marker: &str) -> (String, Option<String>) {
+    match input.split_once(marker) {
+        Some((prefix, suffix)) => (prefix.to_string(), Some(suffix.to_string())),
+        None => (input.to_string(), None),
+    }
+}
+
+/// Reads the prefix and suffix from files, or from stdin split at `marker`.
+pub async fn read_fim_input(
+    prefix_file: Option<&PathBuf>,
+    suffix_file: Option<&PathBuf>,
+    marker: &str,
+) -> Result<(String, Option<String>), Box<dyn std::error::Error>> {
+    let suffix = match suffix_file {
+        Some(path) => Some(read_file(path).await?),
+        None => None,
+    };
+
+    match prefix_file {
+        Some(path) => Ok((read_file(path).await?, suffix)),
+        None => {
+            let mut input = String::new();
+            tokio::io::stdin().read_to_string(&mut input).await?;
+            let (prefix, stdin_suffix) = split_at_marker(&input, marker);
+            Ok((prefix, suffix.or(stdin_suffix)))
+        }
+    }
+}
+
+async fn read_file(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e).into())
+}
+
+/// Streams the completion for the gap straight to stdout, without a trailing newline so
+/// editors can insert the output as is.
+pub async fn run_fim(
+    request: FimCompletionRequest,
+    logger: &Logger,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(100);
+    let logger_clone = logger.clone();
+    let task = tokio::spawn(async move {
+        client::call_mistral_fim(&request, sender, &logger_clone)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    });
+
+    let mut stdout = std::io::stdout();
+    while let Some(event) = receiver.recv().await {
+        if let StreamEvent::Token(token) = event {
+            write!(stdout, "{}", token)?;
+            stdout.flush()?;
+        }
+    }
+
+    task.await??;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_at_marker_with_and_without_cursor() {
+        assert_eq!(
+            split_at_marker(
+                "fn add(a: i32, b: i32) -> i32 {\n    <CURSOR>\n}\n",
+                "<CURSOR>"
+            ),
+            (
+                "fn add(a: i32, b: i32) -> i32 {\n    ".to_string(),
+                Some("\n}\n".to_string())
+            )
+        );
+        assert_eq!(
+            split_at_marker("def fib(n):", "<CURSOR>"),
+            ("def fib(n):".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn fim_request_serializes_optional_fields_only_when_set() {
+        let request = FimCompletionRequest {
+            model: DEFAULT_FIM_MODEL.to_string(),
+            prompt: "def fib(n):".to_string(),
+            suffix: None,
+            stream: true,
+            stop: vec!["\n\n".to_string()],
+            max_tokens: None,
+            temperature: None,
+        };
+
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({
+                "model": "codestral-latest",
+                "prompt": "def fib(n):",
+                "stream": true,
+                "stop": ["\n\n"]
+            })
+        );
+    }
+}
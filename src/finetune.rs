@@ -0,0 +1,207 @@
+use crate::{client, logger::Logger, models::FineTuningJob};
+use std::path::Path;
+
+pub const FINE_TUNE_FILE_PURPOSE: &str = "fine-tune";
+
+const ROLES: [&str; 4] = ["system", "user", "assistant", "tool"];
+// the first problems are enough to fix a broken export
+const MAX_REPORTED_ERRORS: usize = 20;
+
+fn validate_message(message: &serde_json::Value) -> Result<&str, String> {
+    let role = message
+        .get("role")
+        .and_then(|role| role.as_str())
+        .ok_or_else(|| "message without a role".to_string())?;
+    if !ROLES.contains(&role) {
+        return Err(format!("unknown role \"{}\"", role));
+    }
+
+    let has_content = message
+        .get("content")
+        .and_then(|content| content.as_str())
+        .is_some();
+    let has_tool_calls = message
+        .get("tool_calls")
+        .and_then(|tool_calls| tool_calls.as_array())
+        .is_some_and(|tool_calls| !tool_calls.is_empty());
+    // assistant turns may call tools instead of answering
+    if !(has_content || role == "assistant" && has_tool_calls) {
+        return Err(format!("{} message without text content", role));
+    }
+
+    Ok(role)
+}
+
+fn validate_example(line: &str) -> Result<(), String> {
+    let example = serde_json::from_str::<serde_json::Value>(line).map_err(|e| e.to_string())?;
+    let messages = example
+        .get("messages")
+        .and_then(|messages| messages.as_array())
+        .filter(|messages| !messages.is_empty())
+        .ok_or_else(|| "missing \"messages\" list".to_string())?;
+
+    let roles = messages
+        .iter()
+        .map(validate_message)
+        .collect::<Result<Vec<&str>, String>>()?;
+    if !roles.contains(&"assistant") {
+        return Err("no assistant message to learn from".to_string());
+    }
+
+    Ok(())
+}
+
+/// Checks every line is a `{"messages": [...]}` conversation with an assistant answer,
+/// returning the number of examples or the problems found with their line numbers.
+pub fn validate_training_data(data: &str) -> Result<usize, Vec<String>> {
+    let mut examples = 0;
+    let mut errors = Vec::new();
+
+    for (index, line) in data.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match validate_example(line) {
+            Ok(()) => examples += 1,
+            Err(e) if errors.len() < MAX_REPORTED_ERRORS => {
+                errors.push(format!("Line {}: {}", index + 1, e))
+            }
+            Err(_) => {}
+        }
+    }
+
+    if examples == 0 && errors.is_empty() {
+        errors.push("No training examples".to_string());
+    }
+    if errors.is_empty() {
+        Ok(examples)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Validates a training file and uploads it, returning the file id.
+pub async fn upload_training_file(
+    path: &Path,
+    logger: &Logger,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let data = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let examples = validate_training_data(&data).map_err(|errors| {
+        format!(
+            "{} is not valid training data:\n{}",
+            path.display(),
+            errors.join("\n")
+        )
+    })?;
+
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "training.jsonl".to_string());
+    let uploaded =
+        client::upload_file(&file_name, data.as_bytes(), FINE_TUNE_FILE_PURPOSE, logger).await?;
+    eprintln!(
+        "Uploaded {} ({} examples) as {}",
+        file_name, examples, uploaded.id
+    );
+
+    Ok(uploaded.id)
+}
+
+pub fn format_job(job: &FineTuningJob) -> String {
+    let mut lines = vec![
+        format!("Job:        {}", job.id),
+        format!("Status:     {:?}", job.status),
+        format!("Base model: {}", job.model),
+        format!(
+            "Model:      {}",
+            job.fine_tuned_model.as_deref().unwrap_or("-")
+        ),
+        format!("Training:   {}", job.training_files.join(", ")),
+    ];
+    if !job.validation_files.is_empty() {
+        lines.push(format!("Validation: {}", job.validation_files.join(", ")));
+    }
+    if let Some(hyperparameters) = &job.hyperparameters {
+        lines.push(format!(
+            "Parameters: {}",
+            serde_json::to_string(hyperparameters).unwrap_or_default()
+        ));
+    }
+    if let Some(trained_tokens) = job.trained_tokens {
+        lines.push(format!("Tokens:     {}", trained_tokens));
+    }
+
+    lines.join("\n")
+}
+
+pub fn format_job_row(job: &FineTuningJob) -> String {
+    format!(
+        "{}  {:<20}  {}  {}",
+        job.id,
+        format!("{:?}", job.status),
+        job.model,
+        job.fine_tuned_model.as_deref().unwrap_or("-")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::FineTuningJobStatus;
+
+    #[test]
+    fn validate_training_data_reports_lines() {
+        let valid = r#"{"messages": [{"role": "user", "content": "Moi"}, {"role": "assistant", "content": "Moi moi"}]}
+
+{"messages": [{"role": "user", "content": "Sää?"}, {"role": "assistant", "tool_calls": [{"id": "a", "function": {"name": "weather", "arguments": "{}"}}]}, {"role": "tool", "content": "-5"}, {"role": "assistant", "content": "Pakkasta"}]}
+"#;
+        assert_eq!(validate_training_data(valid), Ok(2));
+
+        let invalid = r#"{"messages": [{"role": "user", "content": "Moi"}]}
+{"prompt": "Moi"}
+{"messages": [{"role": "bot", "content": "Moi"}]}
+{"messages": [{"role": "assistant"}]}
+{"messages": "#;
+        let errors = validate_training_data(invalid).unwrap_err();
+        assert_eq!(errors.len(), 5);
+        assert_eq!(errors[0], "Line 1: no assistant message to learn from");
+        assert_eq!(errors[1], "Line 2: missing \"messages\" list");
+        assert_eq!(errors[2], "Line 3: unknown role \"bot\"");
+        assert_eq!(errors[3], "Line 4: assistant message without text content");
+        assert!(errors[4].starts_with("Line 5: EOF"));
+
+        assert_eq!(
+            validate_training_data("\n"),
+            Err(vec!["No training examples".to_string()])
+        );
+    }
+
+    #[test]
+    fn fine_tuning_job_deserializes_and_formats() {
+        let job = serde_json::from_value::<FineTuningJob>(serde_json::json!({
+            "id": "ft_1",
+            "auto_start": true,
+            "model": "open-mistral-7b",
+            "status": "SUCCESS",
+            "job_type": "completion",
+            "created_at": 1750000000,
+            "modified_at": 1750003600,
+            "training_files": ["file_1"],
+            "validation_files": [],
+            "fine_tuned_model": "ft:open-mistral-7b:support:ab12",
+            "suffix": "support",
+            "hyperparameters": {"training_steps": 10, "learning_rate": 0.0001},
+            "trained_tokens": 12000
+        }))
+        .unwrap();
+
+        assert_eq!(job.status, FineTuningJobStatus::Success);
+        let details = format_job(&job);
+        assert!(details.contains("Model:      ft:open-mistral-7b:support:ab12"));
+        assert!(details.contains(r#"Parameters: {"training_steps":10,"learning_rate":0.0001}"#));
+        assert!(!details.contains("Validation:"));
+    }
+}
@@ -0,0 +1,124 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use std::path::Path;
+
+// the API rejects larger images
+const MAX_IMAGE_BYTES: u64 = 10 * 1024 * 1024;
+
+pub fn image_mime_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+    match extension.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+/// Reads an image into a `data:` URL with base64 content for a multimodal message part.
+pub async fn image_data_url(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let mime_type = image_mime_type(path).ok_or_else(|| {
+        format!(
+            "Unsupported image type {}, use png, jpeg, gif or webp",
+            path.display()
+        )
+    })?;
+
+    file_data_url(path, mime_type, MAX_IMAGE_BYTES).await
+}
+
+/// Reads any file into a base64 `data:` URL, failing for files over `max_bytes`.
+pub async fn file_data_url(
+    path: &Path,
+    mime_type: &str,
+    max_bytes: u64,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let metadata = tokio::fs::metadata(path)
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    if metadata.len() > max_bytes {
+        return Err(format!(
+            "{} is larger than {}MB",
+            path.display(),
+            max_bytes / 1024 / 1024
+        )
+        .into());
+    }
+
+    let bytes = tokio::fs::read(path)
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(format!(
+        "data:{};base64,{}",
+        mime_type,
+        STANDARD.encode(bytes)
+    ))
+}
+
+/// File names of the attached images for the prompt header.
+pub fn image_names(paths: &[impl AsRef<Path>]) -> Vec<String> {
+    paths
+        .iter()
+        .map(|path| {
+            let path = path.as_ref();
+            path.file_name()
+                .unwrap_or(path.as_os_str())
+                .to_string_lossy()
+                .to_string()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ChatMessage;
+
+    #[tokio::test]
+    async fn image_data_url_encodes_supported_images() {
+        let path = std::env::temp_dir().join(format!("jwekke-ai-cli-{}.PNG", std::process::id()));
+        std::fs::write(&path, [0x89, b'P', b'N', b'G']).unwrap();
+
+        assert_eq!(
+            image_data_url(&path).await.unwrap(),
+            "data:image/png;base64,iVBORw=="
+        );
+        assert!(
+            image_data_url(Path::new("notes.txt"))
+                .await
+                .unwrap_err()
+                .to_string()
+                .starts_with("Unsupported image type")
+        );
+        assert_eq!(
+            image_names(&[&path]),
+            vec![path.file_name().unwrap().to_string_lossy()]
+        );
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn images_serialize_as_content_parts() {
+        let message = ChatMessage::user_with_images(
+            "What is in the picture?".to_string(),
+            vec!["data:image/png;base64,iVBORw==".to_string()],
+        );
+
+        assert_eq!(
+            serde_json::to_value(&message).unwrap(),
+            serde_json::json!({
+                "role": "user",
+                "content": [
+                    {"type": "text", "text": "What is in the picture?"},
+                    {"type": "image_url", "image_url": "data:image/png;base64,iVBORw=="}
+                ]
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(ChatMessage::user_with_images("moi".to_string(), Vec::new()))
+                .unwrap(),
+            serde_json::json!({"role": "user", "content": "moi"})
+        );
+    }
+}
@@ -0,0 +1,522 @@
+use crate::{embeddings, file, logger::Logger, models::ChatMessage};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const CHUNK_LINES: usize = 40;
+const CHUNK_OVERLAP_LINES: usize = 8;
+// long-lined files get shorter chunks so several still fit in one embedding request
+const MAX_CHUNK_BYTES: usize = 8_000;
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+const SKIPPED_DIRS: [&str; 2] = ["target", "node_modules"];
+
+pub const DEFAULT_TOP_K: usize = 5;
+
+const META_FILE_NAME: &str = "index.json";
+const CHUNKS_FILE_NAME: &str = "chunks.jsonl";
+const VECTORS_FILE_NAME: &str = "vectors.bin";
+
+/// A run of lines from one file, `start_line` and `end_line` are 1-based and inclusive.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexChunk {
+    pub path: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub text: String,
+}
+
+impl IndexChunk {
+    // the location helps the embedding tell similar snippets of different files apart
+    fn embedding_text(&self) -> String {
+        format!(
+            "{}:{}-{}\n{}",
+            self.path, self.start_line, self.end_line, self.text
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexMeta {
+    pub name: String,
+    pub root: PathBuf,
+    pub model: String,
+    pub dimensions: usize,
+    pub chunk_count: usize,
+}
+
+/// An index loaded from disk, `vectors[i]` is the embedding of `chunks[i]`.
+#[derive(Debug, Clone)]
+pub struct LocalIndex {
+    pub meta: IndexMeta,
+    pub chunks: Vec<IndexChunk>,
+    pub vectors: Vec<Vec<f32>>,
+}
+
+/// A retrieved chunk shown under the answer, `number` matches the `[n]` in the context.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Citation {
+    pub number: usize,
+    pub path: PathBuf,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+impl Citation {
+    pub fn label(&self, root: &Path) -> String {
+        format!(
+            "{}:{}-{}",
+            self.path.strip_prefix(root).unwrap_or(&self.path).display(),
+            self.start_line,
+            self.end_line
+        )
+    }
+
+    /// `file://` URI of the cited file, terminals turn it into a clickable link.
+    pub fn uri(&self) -> String {
+        let path = self.path.to_string_lossy();
+        let mut uri = String::from("file://");
+        for byte in path.bytes() {
+            match byte {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                    uri.push(byte as char)
+                }
+                _ => uri.push_str(&format!("%{:02X}", byte)),
+            }
+        }
+        uri
+    }
+}
+
+/// Default index name, the last component of the indexed directory.
+pub fn default_index_name(root: &Path) -> String {
+    root.canonicalize()
+        .ok()
+        .and_then(|path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().to_string())
+        })
+        .unwrap_or_else(|| "default".to_string())
+}
+
+fn is_skipped(path: &Path) -> bool {
+    path.file_name()
+        .map(|name| name.to_string_lossy())
+        .map(|name| name.starts_with('.') || SKIPPED_DIRS.contains(&name.as_ref()))
+        .unwrap_or(false)
+}
+
+/// Files under `root` worth indexing, skipping hidden entries, build output and large
+/// files. Paths are sorted for stable chunk order.
+pub fn collect_source_files(root: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        for entry in std::fs::read_dir(&dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if is_skipped(&path) {
+                continue;
+            }
+
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                pending.push(path);
+            } else if file_type.is_file() && entry.metadata()?.len() <= MAX_FILE_BYTES {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Splits `text` into overlapping chunks of lines, blank chunks are dropped. A chunk
+/// is cut short when its lines go over `MAX_CHUNK_BYTES`.
+pub fn chunk_text(path: &str, text: &str) -> Vec<IndexChunk> {
+    let lines = text.lines().collect::<Vec<&str>>();
+    let mut chunks = Vec::new();
+    let mut start = 0;
+
+    while start < lines.len() {
+        let mut end = (start + CHUNK_LINES).min(lines.len());
+        while end > start + 1
+            && lines[start..end]
+                .iter()
+                .map(|line| line.len() + 1)
+                .sum::<usize>()
+                > MAX_CHUNK_BYTES
+        {
+            end -= 1;
+        }
+        let chunk = lines[start..end].join("\n");
+        if !chunk.trim().is_empty() {
+            chunks.push(IndexChunk {
+                path: path.to_string(),
+                start_line: start + 1,
+                end_line: end,
+                text: chunk,
+            });
+        }
+
+        if end == lines.len() {
+            break;
+        }
+        // chunks cut short have too few lines to overlap
+        let len = end - start;
+        start += if len > CHUNK_OVERLAP_LINES {
+            len - CHUNK_OVERLAP_LINES
+        } else {
+            len
+        };
+    }
+
+    chunks
+}
+
+// files with NUL bytes or invalid UTF-8 are treated as binary
+fn read_text_file(path: &Path) -> Option<String> {
+    let bytes = std::fs::read(path).ok()?;
+    if bytes.contains(&0) {
+        return None;
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Chunks every source file under `root`, paths are stored relative to it.
+pub fn chunk_source_tree(root: &Path) -> std::io::Result<Vec<IndexChunk>> {
+    let mut chunks = Vec::new();
+
+    for path in collect_source_files(root)? {
+        let Some(text) = read_text_file(&path) else {
+            continue;
+        };
+        let relative_path = path.strip_prefix(root).unwrap_or(&path);
+        chunks.extend(chunk_text(&relative_path.to_string_lossy(), &text));
+    }
+
+    Ok(chunks)
+}
+
+/// Chunks and embeds `root` and stores the index under the config dir as `name`.
+pub async fn build_index(
+    root: &Path,
+    name: &str,
+    model: &str,
+    logger: &Logger,
+) -> Result<IndexMeta, Box<dyn std::error::Error>> {
+    let dir = file::get_index_dir(name)?;
+    let root = root.canonicalize()?;
+    let chunks = chunk_source_tree(&root)?;
+    if chunks.is_empty() {
+        return Err(format!("No text files to index in {}", root.display()).into());
+    }
+
+    let texts = chunks
+        .iter()
+        .map(IndexChunk::embedding_text)
+        .collect::<Vec<String>>();
+    let vectors = embeddings::embed_texts(&texts, model, logger, |done, total| {
+        eprint!("\rEmbedded {}/{} chunks", done, total);
+    })
+    .await?;
+    eprintln!();
+
+    let meta = IndexMeta {
+        name: name.to_string(),
+        root,
+        model: model.to_string(),
+        dimensions: vectors.first().map(|vector| vector.len()).unwrap_or(0),
+        chunk_count: chunks.len(),
+    };
+    save_index(&dir, &meta, &chunks, &vectors)?;
+
+    Ok(meta)
+}
+
+pub fn save_index(
+    dir: &Path,
+    meta: &IndexMeta,
+    chunks: &[IndexChunk],
+    vectors: &[Vec<f32>],
+) -> Result<(), Box<dyn std::error::Error>> {
+    std::fs::create_dir_all(dir)?;
+
+    let mut chunk_lines = String::new();
+    for chunk in chunks {
+        chunk_lines.push_str(&serde_json::to_string(chunk)?);
+        chunk_lines.push('\n');
+    }
+    let mut matrix = Vec::new();
+    embeddings::write_matrix(&mut matrix, vectors)?;
+
+    std::fs::write(dir.join(CHUNKS_FILE_NAME), chunk_lines)?;
+    std::fs::write(dir.join(VECTORS_FILE_NAME), matrix)?;
+    std::fs::write(dir.join(META_FILE_NAME), serde_json::to_vec_pretty(meta)?)?;
+
+    Ok(())
+}
+
+pub fn load_index(dir: &Path) -> Result<LocalIndex, Box<dyn std::error::Error>> {
+    let meta = serde_json::from_slice::<IndexMeta>(&std::fs::read(dir.join(META_FILE_NAME))?)?;
+    let chunks = std::fs::read_to_string(dir.join(CHUNKS_FILE_NAME))?
+        .lines()
+        .map(serde_json::from_str::<IndexChunk>)
+        .collect::<Result<Vec<IndexChunk>, _>>()?;
+    let vectors = embeddings::read_matrix(&std::fs::read(dir.join(VECTORS_FILE_NAME))?)?;
+
+    if chunks.len() != vectors.len() {
+        return Err(format!(
+            "Index {} is corrupt: {} chunks but {} vectors",
+            meta.name,
+            chunks.len(),
+            vectors.len()
+        )
+        .into());
+    }
+
+    Ok(LocalIndex {
+        meta,
+        chunks,
+        vectors,
+    })
+}
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot = a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>();
+    let norm =
+        a.iter().map(|x| x * x).sum::<f32>().sqrt() * b.iter().map(|y| y * y).sum::<f32>().sqrt();
+    if norm == 0.0 { 0.0 } else { dot / norm }
+}
+
+impl LocalIndex {
+    pub fn load(name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let dir = file::get_index_dir(name)?;
+        if !dir.join(META_FILE_NAME).exists() {
+            return Err(format!(
+                "No index named {}, build it with `ai index build <dir> --name {}`",
+                name, name
+            )
+            .into());
+        }
+        load_index(&dir)
+    }
+
+    /// The `top_k` chunks most similar to `query`, best match first.
+    pub fn search(&self, query: &[f32], top_k: usize) -> Vec<&IndexChunk> {
+        let mut scored = self
+            .vectors
+            .iter()
+            .map(|vector| cosine_similarity(query, vector))
+            .zip(&self.chunks)
+            .collect::<Vec<(f32, &IndexChunk)>>();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        scored
+            .into_iter()
+            .take(top_k)
+            .map(|(_, chunk)| chunk)
+            .collect()
+    }
+}
+
+/// Builds the messages for a question answered from `chunks`, each chunk is numbered so
+/// the model can cite it as `[n]`.
+pub fn retrieval_messages(
+    question: &str,
+    chunks: &[&IndexChunk],
+    root: &Path,
+) -> (Vec<ChatMessage>, Vec<Citation>) {
+    let mut context = String::from(
+        "Answer the question using the code excerpts below. Cite the excerpts you rely on \
+         as [n]. If the excerpts do not contain the answer, say so.",
+    );
+    let mut citations = Vec::new();
+
+    for (index, chunk) in chunks.iter().enumerate() {
+        let citation = Citation {
+            number: index + 1,
+            path: root.join(&chunk.path),
+            start_line: chunk.start_line,
+            end_line: chunk.end_line,
+        };
+        context.push_str(&format!(
+            "\n\n[{}] {}\n```\n{}\n```",
+            citation.number,
+            citation.label(root),
+            chunk.text
+        ));
+        citations.push(citation);
+    }
+
+    (
+        vec![
+            ChatMessage::new("system", context),
+            ChatMessage::user(question.to_string()),
+        ],
+        citations,
+    )
+}
+
+/// Metadata of every index under the config dir.
+pub fn list_indexes() -> Vec<IndexMeta> {
+    let Ok(entries) = std::fs::read_dir(file::get_indexes_dir()) else {
+        return Vec::new();
+    };
+
+    let mut indexes = entries
+        .flatten()
+        .filter_map(|entry| std::fs::read(entry.path().join(META_FILE_NAME)).ok())
+        .filter_map(|contents| serde_json::from_slice::<IndexMeta>(&contents).ok())
+        .collect::<Vec<IndexMeta>>();
+    indexes.sort_by(|a, b| a.name.cmp(&b.name));
+    indexes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("jwekke-ai-cli-{}-{}", name, std::process::id()));
+        _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn index_names_cannot_leave_the_indexes_dir() {
+        for name in ["", "..", "../config", "a/b", "a\\b"] {
+            assert!(file::get_index_dir(name).is_err(), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn chunk_text_overlaps_and_numbers_lines() {
+        let text = (1..=100)
+            .map(|line| format!("line {}", line))
+            .collect::<Vec<String>>()
+            .join("\n");
+        let chunks = chunk_text("src/lib.rs", &text);
+
+        let ranges = chunks
+            .iter()
+            .map(|chunk| (chunk.start_line, chunk.end_line))
+            .collect::<Vec<(usize, usize)>>();
+        assert_eq!(ranges, vec![(1, 40), (33, 72), (65, 100)]);
+        assert!(chunks[1].text.starts_with("line 33\n"));
+        assert!(chunks[2].text.ends_with("line 100"));
+        assert!(chunk_text("empty.rs", "\n\n  \n").is_empty());
+    }
+
+    #[test]
+    fn long_lines_make_shorter_chunks() {
+        let text = vec!["x".repeat(1000); 20].join("\n");
+        let chunks = chunk_text("min.js", &text);
+
+        let ranges = chunks
+            .iter()
+            .map(|chunk| (chunk.start_line, chunk.end_line))
+            .collect::<Vec<(usize, usize)>>();
+        assert_eq!(ranges, vec![(1, 7), (8, 14), (15, 20)]);
+        assert!(
+            chunks
+                .iter()
+                .all(|chunk| chunk.text.len() <= MAX_CHUNK_BYTES)
+        );
+    }
+
+    #[test]
+    fn source_tree_skips_hidden_build_and_binary_files() {
+        let root = temp_dir("index-walk");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::create_dir_all(root.join("target/debug")).unwrap();
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        std::fs::write(root.join("src/main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(root.join("readme.md"), "# Readme\n").unwrap();
+        std::fs::write(root.join("target/debug/out.rs"), "fn built() {}\n").unwrap();
+        std::fs::write(root.join(".git/HEAD"), "ref: refs/heads/main\n").unwrap();
+        std::fs::write(root.join("logo.png"), [0x89, b'P', b'N', b'G', 0, 1]).unwrap();
+
+        let paths = chunk_source_tree(&root)
+            .unwrap()
+            .into_iter()
+            .map(|chunk| chunk.path)
+            .collect::<Vec<String>>();
+        assert_eq!(paths, vec!["readme.md", "src/main.rs"]);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn search_ranks_by_cosine_similarity() {
+        let chunks = ["auth.rs", "db.rs", "ui.rs"]
+            .iter()
+            .map(|path| chunk_text(path, "fn handler() {}").remove(0))
+            .collect::<Vec<IndexChunk>>();
+        let index = LocalIndex {
+            meta: IndexMeta {
+                name: "demo".to_string(),
+                root: PathBuf::from("/repo"),
+                model: "mistral-embed".to_string(),
+                dimensions: 2,
+                chunk_count: 3,
+            },
+            chunks,
+            vectors: vec![vec![1.0, 0.0], vec![0.0, 1.0], vec![0.7, 0.7]],
+        };
+
+        let paths = index
+            .search(&[0.9, 0.1], 2)
+            .into_iter()
+            .map(|chunk| chunk.path.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(paths, vec!["auth.rs", "ui.rs"]);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn retrieval_messages_number_the_citations() {
+        let chunk = chunk_text("src/auth rs/login.rs", "fn login() {}").remove(0);
+        let (messages, citations) =
+            retrieval_messages("where is auth?", &[&chunk], Path::new("/repo"));
+
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].role, "system");
+        assert!(
+            messages[0]
+                .content
+                .text()
+                .contains("[1] src/auth rs/login.rs:1-1\n```\nfn login() {}\n```")
+        );
+        assert_eq!(messages[1].content.text(), "where is auth?");
+        assert_eq!(
+            citations[0].label(Path::new("/repo")),
+            "src/auth rs/login.rs:1-1"
+        );
+        assert_eq!(citations[0].uri(), "file:///repo/src/auth%20rs/login.rs");
+    }
+
+    #[test]
+    fn index_round_trip() {
+        let dir = temp_dir("index-store");
+        let chunks = chunk_text("src/main.rs", "fn main() {\n    println!(\"moi\");\n}");
+        let meta = IndexMeta {
+            name: "demo".to_string(),
+            root: PathBuf::from("/tmp/demo"),
+            model: "mistral-embed".to_string(),
+            dimensions: 2,
+            chunk_count: chunks.len(),
+        };
+
+        save_index(&dir, &meta, &chunks, &[vec![0.25, -0.5]]).unwrap();
+        let index = load_index(&dir).unwrap();
+
+        assert_eq!(index.meta, meta);
+        assert_eq!(index.chunks, chunks);
+        assert_eq!(index.vectors, vec![vec![0.25, -0.5]]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
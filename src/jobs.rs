@@ -0,0 +1,206 @@
+use crate::{
+    batch::BatchRequest,
+    client,
+    logger::Logger,
+    models::{BatchJob, BatchJobStatus},
+};
+use std::time::Duration;
+
+pub const CHAT_COMPLETIONS_ENDPOINT: &str = "/v1/chat/completions";
+pub const BATCH_FILE_PURPOSE: &str = "batch";
+
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Converts the input to the batch API format of `{"custom_id", "body"}` lines.
+///
+/// Lines already in that format are kept, lines in the `ai batch` format are converted.
+/// The model is set once for the whole job, so lines naming another model are rejected.
+pub fn to_batch_api_input(input: &str, model: &str) -> Result<String, String> {
+    let mut output = String::new();
+
+    for (index, line) in input.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let line_error = |e: String| format!("Line {}: {}", index + 1, e);
+
+        let value = serde_json::from_str::<serde_json::Value>(line)
+            .map_err(|e| line_error(e.to_string()))?;
+        if value.get("body").is_some() {
+            if value.get("custom_id").is_none() {
+                return Err(line_error("Missing custom_id".to_string()));
+            }
+            output.push_str(&value.to_string());
+            output.push('\n');
+            continue;
+        }
+
+        let request =
+            serde_json::from_value::<BatchRequest>(value).map_err(|e| line_error(e.to_string()))?;
+        if let Some(line_model) = request
+            .model
+            .as_deref()
+            .filter(|line_model| *line_model != model)
+        {
+            return Err(line_error(format!(
+                "model {} differs from the job model {}",
+                line_model, model
+            )));
+        }
+
+        let mut body = serde_json::to_value(request.to_chat_request(model).map_err(line_error)?)
+            .map_err(|e| line_error(e.to_string()))?;
+        if let Some(body) = body.as_object_mut() {
+            body.remove("model");
+            body.remove("stream");
+        }
+
+        let custom_id = request.id.unwrap_or_else(|| format!("line-{}", index + 1));
+        output.push_str(&serde_json::json!({"custom_id": custom_id, "body": body}).to_string());
+        output.push('\n');
+    }
+
+    if output.is_empty() {
+        return Err("No requests in the input".to_string());
+    }
+
+    Ok(output)
+}
+
+pub fn format_job(job: &BatchJob) -> String {
+    let mut lines = vec![
+        format!("Job:      {}", job.id),
+        format!("Status:   {:?}", job.status),
+        format!("Model:    {}", job.model.as_deref().unwrap_or("-")),
+        format!(
+            "Progress: {}/{} ({} succeeded, {} failed)",
+            job.completed_requests, job.total_requests, job.succeeded_requests, job.failed_requests
+        ),
+    ];
+    if let Some(output_file) = &job.output_file {
+        lines.push(format!("Output:   {}", output_file));
+    }
+    if let Some(error_file) = &job.error_file {
+        lines.push(format!("Errors:   {}", error_file));
+    }
+    for error in &job.errors {
+        lines.push(format!(
+            "Error:    {} ({}x)",
+            error.message,
+            error.count.unwrap_or(1)
+        ));
+    }
+
+    lines.join("\n")
+}
+
+/// Polls the job until it finishes, printing its progress to stderr.
+pub async fn wait_for_job(
+    job_id: &str,
+    logger: &Logger,
+) -> Result<BatchJob, Box<dyn std::error::Error>> {
+    loop {
+        let job = client::get_batch_job(job_id, logger).await?;
+        if job.status.is_finished() {
+            eprintln!();
+            return Ok(job);
+        }
+
+        eprint!(
+            "\r{:?} {}/{} requests",
+            job.status, job.completed_requests, job.total_requests
+        );
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Output file of a finished job, explaining why there is none otherwise.
+pub fn output_file_id(job: &BatchJob) -> Result<&str, String> {
+    match (&job.output_file, job.status) {
+        (Some(output_file), _) => Ok(output_file),
+        (None, status) if !status.is_finished() => {
+            Err(format!("Job {} is still {:?}", job.id, status))
+        }
+        (None, BatchJobStatus::Cancelled) => Err(format!("Job {} was cancelled", job.id)),
+        (None, _) => Err(format!("Job {} has no output file", job.id)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn batch_job(status: &str, output_file: Option<&str>) -> BatchJob {
+        serde_json::from_value(serde_json::json!({
+            "id": "job_1",
+            "object": "batch",
+            "input_files": ["file_1"],
+            "metadata": null,
+            "endpoint": "/v1/chat/completions",
+            "model": "mistral-small-latest",
+            "output_file": output_file,
+            "error_file": null,
+            "errors": [{"message": "Invalid body", "count": 2}],
+            "status": status,
+            "created_at": 1750000000,
+            "total_requests": 10,
+            "completed_requests": 10,
+            "succeeded_requests": 8,
+            "failed_requests": 2,
+            "started_at": 1750000010,
+            "completed_at": 1750000100
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn batch_input_is_converted_to_custom_id_and_body() {
+        let input = r#"{"id": "a", "prompt": "Moi", "max_tokens": 20}
+{"custom_id": "b", "body": {"messages": [{"role": "user", "content": "Hei"}]}}
+{"prompt": "Terve", "model": "mistral-small-latest"}
+"#;
+        let lines = to_batch_api_input(input, "mistral-small-latest")
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<serde_json::Value>>();
+
+        assert_eq!(
+            lines[0],
+            serde_json::json!({
+                "custom_id": "a",
+                "body": {"messages": [{"role": "user", "content": "Moi"}], "max_tokens": 20}
+            })
+        );
+        assert_eq!(lines[1]["custom_id"], "b");
+        assert_eq!(lines[2]["custom_id"], "line-3");
+
+        assert_eq!(
+            to_batch_api_input(
+                r#"{"prompt": "Moi", "model": "other"}"#,
+                "mistral-small-latest"
+            ),
+            Err("Line 1: model other differs from the job model mistral-small-latest".to_string())
+        );
+        assert_eq!(
+            to_batch_api_input(r#"{"body": {}}"#, "mistral-small-latest"),
+            Err("Line 1: Missing custom_id".to_string())
+        );
+    }
+
+    #[test]
+    fn job_status_and_output_file() {
+        let finished = batch_job("SUCCESS", Some("file_out"));
+        assert_eq!(finished.status, BatchJobStatus::Success);
+        assert_eq!(output_file_id(&finished), Ok("file_out"));
+        assert!(format_job(&finished).contains("Progress: 10/10 (8 succeeded, 2 failed)"));
+        assert!(format_job(&finished).contains("Error:    Invalid body (2x)"));
+
+        let running = batch_job("RUNNING", None);
+        assert_eq!(
+            output_file_id(&running),
+            Err("Job job_1 is still Running".to_string())
+        );
+        assert_eq!(batch_job("PAUSED", None).status, BatchJobStatus::Unknown);
+    }
+}
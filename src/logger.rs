@@ -1,91 +1,561 @@
-use crate::file;
+use crate::{config, file, logs::LogEntry, redact::Redactor};
 use bytes::Bytes;
+use clap::ValueEnum;
 use std::{
-    sync::Arc,
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
     time::{SystemTime, UNIX_EPOCH},
 };
 use tokio::{fs::File, io::AsyncWriteExt, sync::Mutex};
 
-#[derive(Debug, Clone)]
-pub struct Logger {
-    errors: Arc<Mutex<Vec<String>>>,
-    stream_log_file: Arc<Mutex<File>>,
-    error_log_file: Arc<Mutex<File>>,
+const LEVEL_CONFIG_KEY: &str = "LOG_LEVEL";
+const FORMAT_CONFIG_KEY: &str = "LOG_FORMAT";
+const MAX_SIZE_CONFIG_KEY: &str = "LOG_MAX_SIZE";
+const RETENTION_CONFIG_KEY: &str = "LOG_RETENTION";
+
+const DEFAULT_MAX_SIZE: u64 = 10 * 1024 * 1024;
+const DEFAULT_RETENTION: usize = 5;
+// a stream line longer than this is redacted without waiting for its end
+const MAX_PENDING_STREAM_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
 }
 
-// entries: epoch|content
-fn compare_error_log_line(a: &String, b: &String) -> std::cmp::Ordering {
-    let epoch_a = a
-        .split('|')
-        .next()
-        .unwrap_or("0")
-        .parse::<u64>()
-        .unwrap_or(0);
-    let epoch_b = b
-        .split('|')
-        .next()
-        .unwrap_or("0")
+impl LogLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Trace => "TRACE",
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Info => "INFO",
+            LogLevel::Warn => "WARN",
+            LogLevel::Error => "ERROR",
+        }
+    }
+
+    /// Level for `-v` repeated `count` times, starting from the default `warn`.
+    pub fn from_verbosity(count: u8) -> Option<Self> {
+        match count {
+            0 => None,
+            1 => Some(LogLevel::Info),
+            2 => Some(LogLevel::Debug),
+            _ => Some(LogLevel::Trace),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum LogFormat {
+    /// `epoch|LEVEL|run id|message` lines
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+/// Logging settings, `LOG_LEVEL`, `LOG_FORMAT`, `LOG_MAX_SIZE` and `LOG_RETENTION` in the
+/// config with the command line flags taking precedence.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LogOptions {
+    pub level: LogLevel,
+    pub format: LogFormat,
+    /// Size in bytes after which a log file is rotated
+    pub max_size: u64,
+    /// Number of rotated files kept, e.g. `error.log.1` to `error.log.5`
+    pub retention: usize,
+}
+
+impl Default for LogOptions {
+    fn default() -> Self {
+        LogOptions {
+            level: LogLevel::Warn,
+            format: LogFormat::Text,
+            max_size: DEFAULT_MAX_SIZE,
+            retention: DEFAULT_RETENTION,
+        }
+    }
+}
+
+// sizes as bytes or with a K, M or G suffix
+fn parse_size(value: &str) -> Option<u64> {
+    let value = value.trim().to_uppercase();
+    let value = value.strip_suffix('B').unwrap_or(&value);
+    let (number, multiplier) = match value.chars().last()? {
+        'K' => (&value[..value.len() - 1], 1024),
+        'M' => (&value[..value.len() - 1], 1024 * 1024),
+        'G' => (&value[..value.len() - 1], 1024 * 1024 * 1024),
+        _ => (value, 1),
+    };
+    number
+        .trim()
         .parse::<u64>()
-        .unwrap_or(0);
-    epoch_a.cmp(&epoch_b)
+        .ok()
+        .map(|number| number * multiplier)
+}
+
+impl LogOptions {
+    pub fn parse(
+        level: Option<&str>,
+        format: Option<&str>,
+        max_size: Option<&str>,
+        retention: Option<&str>,
+    ) -> Result<Self, String> {
+        let mut options = LogOptions::default();
+        if let Some(level) = level {
+            options.level = LogLevel::from_str(level, true).map_err(|_| {
+                format!(
+                    "Invalid {} \"{}\", use trace, debug, info, warn or error",
+                    LEVEL_CONFIG_KEY, level
+                )
+            })?;
+        }
+        if let Some(format) = format {
+            options.format = LogFormat::from_str(format, true).map_err(|_| {
+                format!(
+                    "Invalid {} \"{}\", use text or json",
+                    FORMAT_CONFIG_KEY, format
+                )
+            })?;
+        }
+        if let Some(max_size) = max_size {
+            options.max_size = parse_size(max_size)
+                .filter(|size| *size > 0)
+                .ok_or_else(|| {
+                    format!(
+                        "Invalid {} \"{}\", use bytes or a size like 10M",
+                        MAX_SIZE_CONFIG_KEY, max_size
+                    )
+                })?;
+        }
+        if let Some(retention) = retention {
+            options.retention = retention
+                .trim()
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid {} \"{}\"", RETENTION_CONFIG_KEY, retention))?;
+        }
+
+        Ok(options)
+    }
+
+    pub async fn from_config(
+        level: Option<LogLevel>,
+        format: Option<LogFormat>,
+    ) -> Result<Self, String> {
+        let mut options = Self::parse(
+            config::get_config_value(LEVEL_CONFIG_KEY).await.as_deref(),
+            config::get_config_value(FORMAT_CONFIG_KEY).await.as_deref(),
+            config::get_config_value(MAX_SIZE_CONFIG_KEY)
+                .await
+                .as_deref(),
+            config::get_config_value(RETENTION_CONFIG_KEY)
+                .await
+                .as_deref(),
+        )?;
+        options.level = level.unwrap_or(options.level);
+        options.format = format.unwrap_or(options.format);
+        Ok(options)
+    }
+}
+
+fn now_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis()
+}
+
+// short random id shared by every line of one run
+fn new_run_id() -> String {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(now_millis());
+    hasher.write_u32(std::process::id());
+    format!("{:012x}", hasher.finish() & 0xffff_ffff_ffff)
+}
+
+/// Escapes backslashes and line breaks so a message or stream chunk stays on one line,
+/// bytes that are not valid UTF-8, e.g. half of a split character, become `\xNN`.
+pub fn escape_line(bytes: &[u8]) -> String {
+    let mut escaped = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        for character in chunk.valid().chars() {
+            match character {
+                '\\' => escaped.push_str("\\\\"),
+                '\n' => escaped.push_str("\\n"),
+                '\r' => escaped.push_str("\\r"),
+                character => escaped.push(character),
+            }
+        }
+        for byte in chunk.invalid() {
+            escaped.push_str(&format!("\\x{:02x}", byte));
+        }
+    }
+    escaped
+}
+
+/// Reverses [`escape_line`], unknown escapes are kept as they are.
+pub fn unescape_line(line: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(line.len());
+    let mut characters = line.char_indices();
+    while let Some((index, character)) = characters.next() {
+        if character != '\\' {
+            let mut buffer = [0; 4];
+            bytes.extend_from_slice(character.encode_utf8(&mut buffer).as_bytes());
+            continue;
+        }
+        match characters.next() {
+            Some((_, '\\')) => bytes.push(b'\\'),
+            Some((_, 'n')) => bytes.push(b'\n'),
+            Some((_, 'r')) => bytes.push(b'\r'),
+            Some((_, 'x')) => match line
+                .get(index + 2..index + 4)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                Some(byte) => {
+                    bytes.push(byte);
+                    characters.nth(1);
+                }
+                None => bytes.extend_from_slice(b"\\x"),
+            },
+            Some((_, other)) => {
+                bytes.push(b'\\');
+                let mut buffer = [0; 4];
+                bytes.extend_from_slice(other.encode_utf8(&mut buffer).as_bytes());
+            }
+            None => bytes.push(b'\\'),
+        }
+    }
+    bytes
+}
+
+/// Moves the complete lines out of `pending` so a redaction pattern sees a secret split
+/// between chunks as a whole. A line growing past `MAX_PENDING_STREAM_BYTES` is taken
+/// as it is.
+fn take_complete_lines(pending: &mut Vec<u8>) -> Option<Vec<u8>> {
+    let end = match pending.iter().rposition(|byte| *byte == b'\n') {
+        Some(index) => index + 1,
+        None if pending.len() > MAX_PENDING_STREAM_BYTES => pending.len(),
+        None => return None,
+    };
+    Some(pending.drain(..end).collect())
+}
+
+/// One log line, text lines keep the `epoch|` prefix and escape line breaks.
+pub fn format_log_line(
+    format: LogFormat,
+    epoch: u128,
+    level: LogLevel,
+    run_id: &str,
+    message: &str,
+) -> String {
+    match format {
+        LogFormat::Text => format!(
+            "{}|{}|{}|{}",
+            epoch,
+            level.as_str(),
+            run_id,
+            escape_line(message.as_bytes())
+        ),
+        LogFormat::Json => serde_json::json!({
+            "ts": epoch as u64,
+            "level": level.as_str(),
+            "run_id": run_id,
+            "message": message,
+        })
+        .to_string(),
+    }
+}
+
+fn rotated_path(path: &Path, number: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", number));
+    PathBuf::from(name)
+}
+
+/// Shifts `log` to `log.1`, `log.1` to `log.2` and so on, dropping files past `retention`.
+pub async fn rotate_files(path: &Path, retention: usize) -> std::io::Result<()> {
+    if retention == 0 {
+        return tokio::fs::remove_file(path).await;
+    }
+
+    _ = tokio::fs::remove_file(rotated_path(path, retention)).await;
+    for number in (1..retention).rev() {
+        let from = rotated_path(path, number);
+        if tokio::fs::try_exists(&from).await.unwrap_or(false) {
+            tokio::fs::rename(from, rotated_path(path, number + 1)).await?;
+        }
+    }
+    tokio::fs::rename(path, rotated_path(path, 1)).await
+}
+
+// an append-mode log file rotated once it grows past `max_size`
+#[derive(Debug)]
+struct LogFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    retention: usize,
+    // set once rotating failed, the file is then appended to past `max_size`
+    rotation_failed: bool,
+}
+
+impl LogFile {
+    async fn open(path: PathBuf, options: &LogOptions) -> Self {
+        let (path, file) = file::open_file(path)
+            .await
+            .unwrap_or_else(|e| panic!("{e}"));
+        let size = file.metadata().await.map(|meta| meta.len()).unwrap_or(0);
+        LogFile {
+            path,
+            file,
+            size,
+            max_size: options.max_size,
+            retention: options.retention,
+            rotation_failed: false,
+        }
+    }
+
+    async fn rotate(&mut self) -> std::io::Result<()> {
+        self.file.flush().await?;
+        rotate_files(&self.path, self.retention).await?;
+        self.file = tokio::fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&self.path)
+            .await?;
+        self.size = 0;
+        Ok(())
+    }
+
+    async fn write_line(&mut self, line: &[u8]) -> std::io::Result<()> {
+        if !self.rotation_failed
+            && self.size > 0
+            && self.size + line.len() as u64 > self.max_size
+            && let Err(e) = self.rotate().await
+        {
+            eprintln!(
+                "Failed to rotate {}: {}, appending to it instead",
+                self.path.display(),
+                e
+            );
+            self.rotation_failed = true;
+        }
+
+        self.file.write_all(line).await?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Logger {
+    options: LogOptions,
+    redactor: Option<Arc<Redactor>>,
+    run_id: Arc<str>,
+    errors: Arc<Mutex<Vec<LogEntry>>>,
+    stream_log_file: Arc<Mutex<LogFile>>,
+    error_log_file: Arc<Mutex<LogFile>>,
+    record_stream: bool,
+    // bytes of a stream waiting for the end of their line to be redacted
+    pending_stream: Arc<Mutex<Vec<u8>>>,
+    // numbers the response streams of a run, compare runs record several at once
+    stream_count: Arc<AtomicUsize>,
+    stream: Option<usize>,
 }
 
 impl Logger {
     pub async fn new() -> Self {
+        Self::with_options(LogOptions::default()).await
+    }
+
+    pub async fn with_options(options: LogOptions) -> Self {
+        let stream_log_file = LogFile::open(file::get_stream_log_file_path(), &options).await;
+        let error_log_file = LogFile::open(file::get_error_log_file_path(), &options).await;
+
         Logger {
-            stream_log_file: Arc::new(Mutex::new(file::create_stream_log_file().await.1)),
-            error_log_file: Arc::new(Mutex::new(file::create_error_log_file().await.1)),
+            options,
+            redactor: None,
+            run_id: new_run_id().into(),
             errors: Arc::new(Mutex::new(Vec::new())),
+            stream_log_file: Arc::new(Mutex::new(stream_log_file)),
+            error_log_file: Arc::new(Mutex::new(error_log_file)),
+            record_stream: true,
+            pending_stream: Arc::new(Mutex::new(Vec::new())),
+            stream_count: Arc::new(AtomicUsize::new(0)),
+            stream: None,
+        }
+    }
+
+    /// Masks what `redactor` matches in every line written to the log files.
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = Some(Arc::new(redactor));
+        self
+    }
+
+    fn redact<'a>(&self, text: &'a str) -> std::borrow::Cow<'a, str> {
+        match &self.redactor {
+            Some(redactor) => redactor.redact(text),
+            None => std::borrow::Cow::Borrowed(text),
         }
     }
 
+    /// Stops recording stream chunks, e.g. while a recorded stream is replayed.
+    pub fn without_stream_log(mut self) -> Self {
+        self.record_stream = false;
+        self
+    }
+
+    /// Correlation id written on every log line of this run.
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// A clone recording one response stream under the next stream number of the run,
+    /// with its own buffer for the line being redacted so concurrent streams don't mix.
+    pub fn for_stream(&self) -> Self {
+        let mut logger = self.clone();
+        logger.pending_stream = Arc::new(Mutex::new(Vec::new()));
+        logger.stream = Some(self.stream_count.fetch_add(1, Ordering::Relaxed) + 1);
+        logger
+    }
+
+    /// Appends a raw response chunk, text lines are escaped so `ai debug replay` can
+    /// rebuild the exact bytes. With redaction on, complete lines are written instead,
+    /// the rest waits for the next chunk or [`Logger::flush_stream_log`].
     pub async fn write_stream_log(&self, bytes: Bytes) {
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis();
+        if !self.record_stream {
+            return;
+        }
+
+        match &self.redactor {
+            Some(redactor) => {
+                let lines = {
+                    let mut pending = self.pending_stream.lock().await;
+                    pending.extend_from_slice(&bytes);
+                    take_complete_lines(&mut pending)
+                };
+                if let Some(lines) = lines {
+                    self.write_stream_record(&redactor.redact_bytes(&lines))
+                        .await;
+                }
+            }
+            None => self.write_stream_record(&bytes).await,
+        }
+    }
+
+    /// Writes what is left of an unfinished line once the stream ends.
+    pub async fn flush_stream_log(&self) {
+        let Some(redactor) = self.redactor.as_ref().filter(|_| self.record_stream) else {
+            return;
+        };
+        let rest = std::mem::take(&mut *self.pending_stream.lock().await);
+        if !rest.is_empty() {
+            self.write_stream_record(&redactor.redact_bytes(&rest))
+                .await;
+        }
+    }
+
+    async fn write_stream_record(&self, chunk: &[u8]) {
+        let now = now_millis();
 
         let mut buffer = Vec::new();
-        buffer.extend_from_slice(format!("{now}|").as_bytes());
-        buffer.extend_from_slice(&bytes);
+        match self.options.format {
+            LogFormat::Text => {
+                let stream = self
+                    .stream
+                    .map(|stream| format!("/{}", stream))
+                    .unwrap_or_default();
+                buffer.extend_from_slice(format!("{now}|{}{}|", self.run_id, stream).as_bytes());
+                buffer.extend_from_slice(escape_line(chunk).as_bytes());
+            }
+            // a chunk that is not valid UTF-8 is kept escaped instead of mangled
+            LogFormat::Json => {
+                let mut record = serde_json::json!({
+                    "ts": now as u64,
+                    "run_id": &*self.run_id,
+                });
+                if let Some(stream) = self.stream {
+                    record["stream"] = stream.into();
+                }
+                match std::str::from_utf8(chunk) {
+                    Ok(text) => record["chunk"] = text.into(),
+                    Err(_) => record["chunk_escaped"] = escape_line(chunk).into(),
+                }
+                buffer.extend_from_slice(record.to_string().as_bytes());
+            }
+        }
         buffer.push(b'\n');
 
         self.stream_log_file
             .lock()
             .await
-            .write_all(&buffer)
+            .write_line(&buffer)
             .await
             .unwrap_or_else(|e| panic!("{e}"));
     }
 
-    pub async fn get_sorted_errors(&self) -> Vec<String> {
-        // Sort the errors based on the epoch before writing
-        let mut errors = self.get_errors().await;
-        errors.sort_by(compare_error_log_line);
-        errors
-    }
-
-    pub async fn log_error(&self, error: String) {
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis();
-
-        let mut errors = self.errors.lock().await;
-        errors.push(format!("{}|{}", now, error));
-        drop(errors);
+    /// Appends `message` to error.log when `level` is enabled, warnings and errors are
+    /// also kept for the error panel of the TUI.
+    pub async fn log(&self, level: LogLevel, message: String) {
+        let now = now_millis();
+        if level >= LogLevel::Warn {
+            self.errors.lock().await.push(LogEntry {
+                epoch: now as u64,
+                level: Some(level),
+                run_id: Some(self.run_id.to_string()),
+                message: message.clone(),
+            });
+        }
+        if level < self.options.level {
+            return;
+        }
 
-        let errors = &self.get_sorted_errors().await;
-        self.error_log_file.lock().await.flush().await.unwrap();
+        let mut line = format_log_line(
+            self.options.format,
+            now,
+            level,
+            &self.run_id,
+            &self.redact(&message),
+        );
+        line.push('\n');
         self.error_log_file
             .lock()
             .await
-            .write_all(errors.join("\n").as_bytes())
+            .write_line(line.as_bytes())
             .await
-            .unwrap();
+            .unwrap_or_else(|e| panic!("{e}"));
+    }
+
+    pub async fn log_error(&self, error: String) {
+        self.log(LogLevel::Error, error).await;
+    }
+
+    pub async fn log_warn(&self, message: String) {
+        self.log(LogLevel::Warn, message).await;
+    }
+
+    pub async fn log_info(&self, message: String) {
+        self.log(LogLevel::Info, message).await;
     }
 
-    pub async fn get_errors(&self) -> Vec<String> {
+    pub async fn log_debug(&self, message: String) {
+        self.log(LogLevel::Debug, message).await;
+    }
+
+    pub async fn log_trace(&self, message: String) {
+        self.log(LogLevel::Trace, message).await;
+    }
+
+    pub async fn get_errors(&self) -> Vec<LogEntry> {
         let errors = self.errors.lock().await;
         errors.clone()
     }
@@ -103,3 +573,141 @@ impl Default for Logger {
             .block_on(Self::new())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_parse_config_values() {
+        let options =
+            LogOptions::parse(Some("Debug"), Some("json"), Some("2M"), Some("3")).unwrap();
+
+        assert_eq!(options.level, LogLevel::Debug);
+        assert_eq!(options.format, LogFormat::Json);
+        assert_eq!(options.max_size, 2 * 1024 * 1024);
+        assert_eq!(options.retention, 3);
+        assert_eq!(
+            LogOptions::parse(None, None, None, None).unwrap(),
+            LogOptions::default()
+        );
+        assert_eq!(parse_size("512"), Some(512));
+        assert_eq!(parse_size("64kb"), Some(64 * 1024));
+        assert!(LogOptions::parse(Some("loud"), None, None, None).is_err());
+        assert!(LogOptions::parse(None, None, Some("0"), None).is_err());
+        assert_eq!(LogLevel::from_verbosity(2), Some(LogLevel::Debug));
+        assert_eq!(LogLevel::from_verbosity(7), Some(LogLevel::Trace));
+    }
+
+    #[test]
+    fn log_lines_stay_on_one_line() {
+        assert_eq!(
+            format_log_line(
+                LogFormat::Text,
+                1750000000000,
+                LogLevel::Warn,
+                "ab12",
+                "Client Error: 429\n{\"message\": \"slow down\"}"
+            ),
+            "1750000000000|WARN|ab12|Client Error: 429\\n{\"message\": \"slow down\"}"
+        );
+
+        let line = format_log_line(LogFormat::Json, 1, LogLevel::Error, "ab12", "a\nb");
+        assert!(!line.contains('\n'));
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&line).unwrap(),
+            serde_json::json!({"ts": 1, "level": "ERROR", "run_id": "ab12", "message": "a\nb"})
+        );
+    }
+
+    #[test]
+    fn escaped_chunks_round_trip() {
+        let chunk = "data: {\"content\":\"a\\\\b\\n\"}\n\ndata: [DONE]\r\n".as_bytes();
+        let escaped = escape_line(chunk);
+        assert!(!escaped.contains('\n'));
+        assert_eq!(unescape_line(&escaped), chunk);
+
+        // a character split between two chunks
+        let split = &"ä".as_bytes()[..1];
+        assert_eq!(escape_line(split), "\\xc3");
+        assert_eq!(unescape_line("\\xc3"), split);
+        assert_eq!(unescape_line("\\q\\x4"), b"\\q\\x4");
+    }
+
+    #[test]
+    fn secrets_split_between_chunks_are_redacted_whole() {
+        let redactor = Redactor::new(false, None, &[]).unwrap();
+        let mut pending = Vec::new();
+
+        pending.extend_from_slice(b"data: {\"api_key\": \"sk-12");
+        assert_eq!(take_complete_lines(&mut pending), None);
+        pending.extend_from_slice(b"3456789\"}\n\ndata: {\"content\":\"\xc3");
+        let lines = take_complete_lines(&mut pending).unwrap();
+
+        assert_eq!(
+            redactor.redact_bytes(&lines),
+            b"data: {\"api_key\": \"[REDACTED]\"}\n\n"
+        );
+        assert_eq!(pending, b"data: {\"content\":\"\xc3");
+    }
+
+    #[tokio::test]
+    async fn rotation_keeps_the_configured_number_of_files() {
+        let dir = std::env::temp_dir().join(format!("jwekke-logger-test-{}", new_run_id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("error.log");
+
+        for round in 1..=4 {
+            tokio::fs::write(&path, format!("round {}", round))
+                .await
+                .unwrap();
+            rotate_files(&path, 2).await.unwrap();
+        }
+
+        assert!(!path.exists());
+        assert_eq!(
+            tokio::fs::read_to_string(rotated_path(&path, 1))
+                .await
+                .unwrap(),
+            "round 4"
+        );
+        assert_eq!(
+            tokio::fs::read_to_string(rotated_path(&path, 2))
+                .await
+                .unwrap(),
+            "round 3"
+        );
+        assert!(!rotated_path(&path, 3).exists());
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn failed_rotation_keeps_appending() {
+        let dir = std::env::temp_dir().join(format!("jwekke-logger-test-{}", new_run_id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("stream.log");
+        // a non-empty directory in the way of `stream.log.1` makes the rename fail
+        tokio::fs::create_dir_all(rotated_path(&path, 1).join("blocker"))
+            .await
+            .unwrap();
+
+        let options = LogOptions {
+            max_size: 8,
+            retention: 1,
+            ..LogOptions::default()
+        };
+        let mut log_file = LogFile::open(path.clone(), &options).await;
+        log_file.write_line(b"first line\n").await.unwrap();
+        log_file.write_line(b"second line\n").await.unwrap();
+        log_file.file.flush().await.unwrap();
+
+        assert!(log_file.rotation_failed);
+        assert_eq!(
+            tokio::fs::read_to_string(&path).await.unwrap(),
+            "first line\nsecond line\n"
+        );
+
+        tokio::fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
@@ -0,0 +1,392 @@
+use crate::{
+    logger::{self, LogLevel},
+    replay::RecordedChunk,
+};
+use clap::ValueEnum;
+use regex::Regex;
+use std::{io::SeekFrom, path::Path, time::Duration};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+const MILLIS_PER_DAY: u64 = 86_400_000;
+
+/// One entry of `error.log`, lines written before levels and run ids were added have none.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogEntry {
+    pub epoch: u64,
+    pub level: Option<LogLevel>,
+    pub run_id: Option<String>,
+    pub message: String,
+}
+
+fn parse_json_entry(line: &str) -> Option<LogEntry> {
+    let value = serde_json::from_str::<serde_json::Value>(line).ok()?;
+    Some(LogEntry {
+        epoch: value.get("ts")?.as_u64()?,
+        level: value
+            .get("level")
+            .and_then(|level| level.as_str())
+            .and_then(|level| LogLevel::from_str(level, true).ok()),
+        run_id: value
+            .get("run_id")
+            .and_then(|run_id| run_id.as_str())
+            .map(str::to_string),
+        message: value.get("message")?.as_str()?.to_string(),
+    })
+}
+
+fn parse_text_entry(epoch: u64, rest: &str) -> LogEntry {
+    let mut fields = rest.splitn(3, '|');
+    if let (Some(level), Some(run_id), Some(message)) =
+        (fields.next(), fields.next(), fields.next())
+        && let Some(level) = LogLevel::value_variants()
+            .iter()
+            .find(|variant| variant.as_str() == level)
+    {
+        return LogEntry {
+            epoch,
+            level: Some(*level),
+            run_id: Some(run_id.to_string()),
+            message: String::from_utf8_lossy(&logger::unescape_line(message)).into_owned(),
+        };
+    }
+
+    LogEntry {
+        epoch,
+        level: None,
+        run_id: None,
+        message: rest.to_string(),
+    }
+}
+
+/// Reads `error.log` in the text or JSON format. The old `epoch|message` lines are read
+/// too, their multi-line messages continue on lines without an epoch.
+pub fn parse_error_log(text: &str) -> Vec<LogEntry> {
+    let mut entries: Vec<LogEntry> = Vec::new();
+
+    for line in text.lines() {
+        if line.starts_with('{')
+            && let Some(entry) = parse_json_entry(line)
+        {
+            entries.push(entry);
+            continue;
+        }
+
+        match line
+            .split_once('|')
+            .and_then(|(epoch, rest)| Some((epoch.parse::<u64>().ok()?, rest)))
+        {
+            Some((epoch, rest)) => entries.push(parse_text_entry(epoch, rest)),
+            None => {
+                if let Some(previous) = entries.last_mut().filter(|entry| entry.level.is_none()) {
+                    previous.message.push('\n');
+                    previous.message.push_str(line);
+                }
+            }
+        }
+    }
+
+    entries
+}
+
+// days since 1970-01-01 to a proleptic Gregorian date
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+fn days_from_civil(year: u64, month: u64, day: u64) -> Option<u64> {
+    let year = year.checked_sub(u64::from(month <= 2))?;
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let month_index = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    (era * 146_097 + day_of_era).checked_sub(719_468)
+}
+
+/// `2026-01-31 14:05:09.120` in UTC.
+pub fn format_timestamp(epoch: u64) -> String {
+    let (year, month, day) = civil_from_days(epoch / MILLIS_PER_DAY);
+    let millis_of_day = epoch % MILLIS_PER_DAY;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:03}",
+        year,
+        month,
+        day,
+        millis_of_day / 3_600_000,
+        millis_of_day / 60_000 % 60,
+        millis_of_day / 1000 % 60,
+        millis_of_day % 1000
+    )
+}
+
+fn parse_utc_time(value: &str) -> Option<u64> {
+    let (date, time) = match value.split_once([' ', 'T']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (value, None),
+    };
+
+    let mut date_parts = date.split('-').map(|part| part.parse::<u64>().ok());
+    let (year, month, day) = (
+        date_parts.next()??,
+        date_parts.next()??,
+        date_parts.next()??,
+    );
+    if date_parts.next().is_some() || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let mut seconds = 0;
+    if let Some(time) = time {
+        let parts = time
+            .split(':')
+            .map(|part| part.parse::<u64>().ok())
+            .collect::<Option<Vec<u64>>>()?;
+        let (hours, minutes, secs) = match parts[..] {
+            [hours, minutes] => (hours, minutes, 0),
+            [hours, minutes, secs] => (hours, minutes, secs),
+            _ => return None,
+        };
+        if hours > 23 || minutes > 59 || secs > 59 {
+            return None;
+        }
+        seconds = hours * 3600 + minutes * 60 + secs;
+    }
+
+    Some(days_from_civil(year, month, day)? * MILLIS_PER_DAY + seconds * 1000)
+}
+
+/// Epoch in milliseconds for `--since`, a duration back from `now` such as `30m`, `2h`
+/// or `7d`, or a UTC time such as `2026-01-31` or `2026-01-31 14:00`.
+pub fn parse_since(value: &str, now: u64) -> Result<u64, String> {
+    let value = value.trim();
+    let unit = match value.chars().last() {
+        Some('s') => Some(1000),
+        Some('m') => Some(60 * 1000),
+        Some('h') => Some(3_600_000),
+        Some('d') => Some(MILLIS_PER_DAY),
+        Some('w') => Some(7 * MILLIS_PER_DAY),
+        _ => None,
+    };
+    if let Some(unit) = unit
+        && let Ok(count) = value[..value.len() - 1].trim().parse::<u64>()
+    {
+        return Ok(now.saturating_sub(count * unit));
+    }
+
+    parse_utc_time(value).ok_or_else(|| {
+        format!(
+            "Invalid --since \"{}\", use a duration like 30m, 2h or 7d, or a UTC time like 2026-01-31 14:00",
+            value
+        )
+    })
+}
+
+#[derive(Debug, Default)]
+pub struct LogFilter {
+    pub since: Option<u64>,
+    pub grep: Option<Regex>,
+    pub level: Option<LogLevel>,
+    pub run_id: Option<String>,
+}
+
+impl LogFilter {
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        self.since.is_none_or(|since| entry.epoch >= since)
+            // entries without a level were all errors
+            && self
+                .level
+                .is_none_or(|level| entry.level.unwrap_or(LogLevel::Error) >= level)
+            && self
+                .run_id
+                .as_ref()
+                .is_none_or(|run_id| entry.run_id.as_ref() == Some(run_id))
+            && self
+                .grep
+                .as_ref()
+                .is_none_or(|grep| grep.is_match(&entry.message))
+    }
+}
+
+pub fn format_entry(entry: &LogEntry) -> String {
+    format!(
+        "{} {:<5} {} {}",
+        format_timestamp(entry.epoch),
+        entry.level.unwrap_or(LogLevel::Error).as_str(),
+        entry.run_id.as_deref().unwrap_or("-"),
+        entry.message
+    )
+}
+
+/// The time of day, level and first line of an entry for the TUI's error panel.
+pub fn format_summary(entry: &LogEntry) -> String {
+    let mut lines = entry.message.lines();
+    let mut summary = format!(
+        "{} {:<5} {}",
+        &format_timestamp(entry.epoch)[11..],
+        entry.level.unwrap_or(LogLevel::Error).as_str(),
+        lines.next().unwrap_or_default()
+    );
+    let more = lines.count();
+    if more > 0 {
+        summary.push_str(&format!(" (+{} lines)", more));
+    }
+    summary
+}
+
+/// One line per stream chunk, its line breaks escaped as in the text log.
+pub fn format_chunk(chunk: &RecordedChunk) -> String {
+    let stream = chunk
+        .stream
+        .map(|stream| format!("/{}", stream))
+        .unwrap_or_default();
+    format!(
+        "{} {}{} {}",
+        format_timestamp(chunk.epoch),
+        chunk.run_id.as_deref().unwrap_or("-"),
+        stream,
+        logger::escape_line(&chunk.bytes)
+    )
+}
+
+/// Reads the whole file, returning its text and the offset to follow from.
+pub async fn read_log(path: &Path) -> Result<(String, u64), Box<dyn std::error::Error>> {
+    let bytes = tokio::fs::read(path)
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok((
+        String::from_utf8_lossy(&bytes).into_owned(),
+        bytes.len() as u64,
+    ))
+}
+
+/// Polls the file for lines appended after `offset` and hands them to `on_lines` until
+/// the process is stopped. A file smaller than the offset was rotated and is read anew.
+pub async fn follow(
+    path: &Path,
+    mut offset: u64,
+    mut on_lines: impl FnMut(&str),
+) -> std::io::Result<()> {
+    let mut pending = Vec::new();
+
+    loop {
+        tokio::time::sleep(FOLLOW_INTERVAL).await;
+        let len = match tokio::fs::metadata(path).await {
+            Ok(metadata) => metadata.len(),
+            Err(_) => continue,
+        };
+        if len < offset {
+            offset = 0;
+            pending.clear();
+        }
+        if len == offset {
+            continue;
+        }
+
+        let mut file = tokio::fs::File::open(path).await?;
+        file.seek(SeekFrom::Start(offset)).await?;
+        let read = file.read_to_end(&mut pending).await?;
+        offset += read as u64;
+
+        if let Some(end) = pending.iter().rposition(|byte| *byte == b'\n') {
+            let lines = pending.drain(..=end).collect::<Vec<u8>>();
+            on_lines(&String::from_utf8_lossy(&lines));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_log_formats_are_read_back() {
+        let log = "1750000000000|Client Error: 500 Internal Server Error\n\
+                   {\"message\": \"boom\"}\n\
+                   1750000000500|WARN|0123456789ab|Client Error: 429, retrying\\nslow down\n\
+                   {\"ts\":1750000001000,\"level\":\"ERROR\",\"run_id\":\"0123456789ab\",\"message\":\"a|b\"}\n";
+        let entries = parse_error_log(log);
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(
+            entries[0].message,
+            "Client Error: 500 Internal Server Error\n{\"message\": \"boom\"}"
+        );
+        assert_eq!(entries[0].level, None);
+        assert_eq!(entries[1].level, Some(LogLevel::Warn));
+        assert_eq!(entries[1].message, "Client Error: 429, retrying\nslow down");
+        assert_eq!(entries[2].run_id.as_deref(), Some("0123456789ab"));
+        assert_eq!(entries[2].message, "a|b");
+        assert_eq!(
+            format_entry(&entries[1]),
+            "2025-06-15 15:06:40.500 WARN  0123456789ab Client Error: 429, retrying\nslow down"
+        );
+        assert_eq!(
+            format_summary(&entries[1]),
+            "15:06:40.500 WARN  Client Error: 429, retrying (+1 lines)"
+        );
+    }
+
+    #[test]
+    fn since_accepts_durations_and_utc_times() {
+        let now = 1_750_000_000_000;
+
+        assert_eq!(parse_since("30m", now), Ok(now - 30 * 60 * 1000));
+        assert_eq!(parse_since("2d", now), Ok(now - 2 * MILLIS_PER_DAY));
+        assert_eq!(parse_since("2025-06-15", now), Ok(1_749_945_600_000));
+        assert_eq!(parse_since("2025-06-15 15:06:40", now), Ok(now));
+        assert_eq!(parse_since("2024-02-29T00:00", now), Ok(1_709_164_800_000));
+        assert!(parse_since("yesterday", now).is_err());
+        assert!(parse_since("2025-13-01", now).is_err());
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00.000");
+        assert_eq!(
+            format_timestamp(1_709_164_800_000),
+            "2024-02-29 00:00:00.000"
+        );
+    }
+
+    #[test]
+    fn filter_combines_every_condition() {
+        let entries = parse_error_log(
+            "1000|INFO|0123456789ab|Started run (version 0.1.0)\n\
+             2000|ERROR|0123456789ab|Client Error: 401 Unauthorized\n\
+             3000|ERROR|ba9876543210|Client Error: 500\n",
+        );
+        let filter = LogFilter {
+            since: Some(1500),
+            grep: Some(Regex::new("Client Error: 4").unwrap()),
+            level: Some(LogLevel::Warn),
+            run_id: Some("0123456789ab".to_string()),
+        };
+
+        assert_eq!(
+            entries
+                .iter()
+                .filter(|entry| filter.matches(entry))
+                .map(|entry| entry.epoch)
+                .collect::<Vec<u64>>(),
+            vec![2000]
+        );
+        assert_eq!(
+            entries
+                .iter()
+                .filter(|entry| LogFilter::default().matches(entry))
+                .count(),
+            3
+        );
+    }
+}
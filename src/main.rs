@@ -1,37 +1,1064 @@
-use crate::logger::Logger;
-use clap::{Parser, Subcommand};
+use crate::{
+    embeddings::EmbeddingFormat,
+    index::{Citation, LocalIndex},
+    logger::{LogFormat, LogLevel, LogOptions, Logger},
+    models::{
+        BatchJobRequest, ChatCompletionRequest, ChatMessage, FimCompletionRequest,
+        FineTuningHyperparameters, FineTuningJobRequest, ModelCapability, ModerationRequest,
+        OcrRequest, TrainingFile,
+    },
+    ocr::OcrFormat,
+    redact::Redactor,
+    stream::StreamEvent,
+    structured::JsonOutput,
+    tools::ToolRegistry,
+    transcribe::TranscriptFormat,
+    ui::StreamPane,
+};
+use clap::{ArgGroup, CommandFactory, FromArgMatches, Parser, Subcommand};
+use std::path::PathBuf;
 
+pub mod agents;
+pub mod batch;
 pub mod client;
+pub mod clipboard;
 pub mod config;
+pub mod embeddings;
+pub mod export;
 pub mod file;
+pub mod fim;
+pub mod finetune;
+pub mod images;
+pub mod index;
+pub mod jobs;
 pub mod logger;
+pub mod logs;
+pub mod markdown;
+pub mod mcp;
 pub mod models;
+pub mod moderation;
+pub mod ocr;
+pub mod redact;
+pub mod replay;
+pub mod resolver;
+pub mod search;
 pub mod stream;
+pub mod structured;
+pub mod tools;
+pub mod transcribe;
 pub mod ui;
 
+// how long streams may keep running once the UI has closed
+const STREAM_SHUTDOWN_GRACE: std::time::Duration = std::time::Duration::from_secs(2);
+
 /// A CLI tool for interacting with the Mistral AI API
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Log more to error.log, -v for info, -vv for debug and -vvv for trace
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+    /// Lowest level written to error.log, overrides -v and LOG_LEVEL
+    #[arg(long, value_enum, global = true)]
+    log_level: Option<LogLevel>,
+    /// Format of error.log and stream.log, overrides LOG_FORMAT
+    #[arg(long, value_enum, global = true)]
+    log_format: Option<LogFormat>,
 }
 
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Run a prompt through the Mistral AI API
+    #[command(group(ArgGroup::new("json_or_schema").args(["json", "schema"]).multiple(true)))]
     Run {
         /// The prompt to send to the Mistral AI API
         prompt: String,
+        /// Model to use for this run only, fuzzy-matched against the cached model list
+        #[arg(short, long)]
+        model: Option<String>,
+        /// Let the model call the built-in local tools and the configured MCP servers
+        #[arg(long)]
+        tools: bool,
+        /// Ask for a JSON object answer (`response_format: json_object`)
+        #[arg(long)]
+        json: bool,
+        /// Ask for JSON matching this JSON schema file and validate the answer against it
+        #[arg(long, value_name = "SCHEMA_FILE")]
+        schema: Option<PathBuf>,
+        /// Re-prompt the model with the validation errors when the JSON answer is invalid,
+        /// needs --json or --schema
+        #[arg(long, requires = "json_or_schema")]
+        repair: bool,
+        /// Print the answer to stdout without the TUI, JSON runs print only the validated JSON
+        #[arg(long)]
+        plain: bool,
+        /// Attach an image (png, jpeg, gif or webp), repeat the flag for several. With
+        /// --agent or --conversation the agent's model decides whether images are accepted
+        #[arg(long = "image", value_name = "PATH")]
+        images: Vec<PathBuf>,
+        /// Send the prompt to an agent from the Mistral console instead of a model
+        #[arg(long, value_name = "AGENT_ID", conflicts_with_all = ["model", "tools", "json", "schema"])]
+        agent: Option<String>,
+        /// Continue a stored agent conversation, `last` for the most recent one
+        #[arg(long, value_name = "CONVERSATION_ID", conflicts_with_all = ["agent", "model", "tools", "json", "schema"])]
+        conversation: Option<String>,
+    },
+    /// Answer a question from a local code index, citing the retrieved chunks
+    Ask {
+        /// The question to answer
+        question: String,
+        /// Name of the index built with `ai index build`
+        #[arg(long)]
+        index: String,
+        /// Number of chunks to retrieve
+        #[arg(short = 'k', long, default_value_t = index::DEFAULT_TOP_K)]
+        top_k: usize,
+        /// Model to answer with, fuzzy-matched against the cached model list
+        #[arg(short, long)]
+        model: Option<String>,
+        /// Print the answer and sources to stdout without the TUI
+        #[arg(long)]
+        plain: bool,
+    },
+    /// Stream the same prompt through several models side by side
+    Compare {
+        /// The prompt to send to every model
+        prompt: String,
+        /// Models to compare, repeat the flag for each model
+        #[arg(short = 'm', long = "model", required = true)]
+        models: Vec<String>,
+        /// Write the comparison as markdown to this file after the run
+        #[arg(long)]
+        export: Option<PathBuf>,
+    },
+    /// Run many prompts from a JSONL file without streaming
+    Batch {
+        /// JSONL file with one {"id", "prompt" or "messages", "model", ...} request per line
+        input: PathBuf,
+        /// JSONL file the results are appended to, completed ids are skipped on a rerun
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Default model for lines without one, fuzzy-matched against the cached model list
+        #[arg(short, long)]
+        model: Option<String>,
+        /// Number of requests in flight at once
+        #[arg(short, long, default_value_t = batch::DEFAULT_CONCURRENCY)]
+        concurrency: usize,
+        /// Upper limit of requests started per minute
+        #[arg(long)]
+        requests_per_minute: Option<u32>,
+    },
+    /// Run large offline jobs with the provider-side batch API
+    Jobs {
+        #[command(subcommand)]
+        command: JobsCommands,
+    },
+    /// Manage fine-tuning jobs
+    Finetune {
+        #[command(subcommand)]
+        command: FinetuneCommands,
+    },
+    /// Fill in the middle of code, for editor integrations
+    Fim {
+        /// File with the code before the cursor, stdin is read when omitted
+        #[arg(long)]
+        prefix_file: Option<PathBuf>,
+        /// File with the code after the cursor
+        #[arg(long)]
+        suffix_file: Option<PathBuf>,
+        /// Cursor marker splitting stdin into prefix and suffix
+        #[arg(long, default_value = fim::DEFAULT_CURSOR_MARKER)]
+        marker: String,
+        /// FIM capable model, defaults to codestral-latest
+        #[arg(short, long)]
+        model: Option<String>,
+        /// Stop sequence, repeat the flag for several
+        #[arg(long)]
+        stop: Vec<String>,
+        /// Maximum number of tokens to generate
+        #[arg(long)]
+        max_tokens: Option<u32>,
+        /// Sampling temperature
+        #[arg(long)]
+        temperature: Option<f64>,
+    },
+    /// Extract text from a PDF or an image
+    Ocr {
+        /// PDF or image file, or an http(s) URL of one
+        input: String,
+        /// OCR capable model, defaults to mistral-ocr-latest
+        #[arg(short, long)]
+        model: Option<String>,
+        /// Output format
+        #[arg(long, value_enum, default_value = "markdown")]
+        format: OcrFormat,
+        /// Write the result to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Send the recognized text with this prompt to the configured model
+        #[arg(long, value_name = "PROMPT")]
+        run: Option<String>,
+        /// Print the follow-up answer to stdout without the TUI
+        #[arg(long)]
+        plain: bool,
+    },
+    /// Transcribe a local audio recording
+    Transcribe {
+        /// Audio file (wav, mp3, m4a, flac, ogg or webm)
+        file: PathBuf,
+        /// Audio capable model, defaults to voxtral-mini-latest
+        #[arg(short, long)]
+        model: Option<String>,
+        /// Language hint as an ISO 639-1 code, e.g. fi
+        #[arg(short, long)]
+        language: Option<String>,
+        /// Output format
+        #[arg(long, value_enum, default_value = "text")]
+        format: TranscriptFormat,
+        /// Prefix every text line with its segment timestamps
+        #[arg(long)]
+        timestamps: bool,
+        /// Write the transcript to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Print moderation category scores for texts or stdin
+    Moderate {
+        /// Texts to check, stdin is read when omitted
+        texts: Vec<String>,
+        /// Moderation model, defaults to mistral-moderation-latest
+        #[arg(short, long)]
+        model: Option<String>,
+    },
+    /// Print the scores of a classifier model for texts or stdin
+    Classify {
+        /// Texts to classify, stdin is read when omitted
+        texts: Vec<String>,
+        /// Classifier model, e.g. a fine-tuned classifier
+        #[arg(short, long)]
+        model: String,
+    },
+    /// Embed texts or files and write the vectors
+    Embed {
+        /// Texts to embed, stdin is read when neither texts nor files are given
+        texts: Vec<String>,
+        /// File to embed as one input, repeat the flag for several
+        #[arg(long = "file")]
+        files: Vec<PathBuf>,
+        /// Embedding model, defaults to mistral-embed
+        #[arg(short, long)]
+        model: Option<String>,
+        /// Output format
+        #[arg(long, value_enum, default_value = "jsonl")]
+        format: EmbeddingFormat,
+        /// Write the vectors to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// List the agents and the stored agent conversations
+    Agents {
+        #[command(subcommand)]
+        command: AgentsCommands,
+    },
+    /// Manage local vector indexes of source trees
+    Index {
+        #[command(subcommand)]
+        command: IndexCommands,
+    },
+    /// Browse and search error.log and stream.log
+    Logs {
+        #[command(subcommand)]
+        command: LogsCommands,
+    },
+    /// Tools for debugging the CLI itself
+    Debug {
+        #[command(subcommand)]
+        command: DebugCommands,
     },
     /// Configure the Mistral AI model
     Config,
+    /// List the configured MCP servers and the tools they expose
+    Mcp,
+}
+
+#[derive(Subcommand, Debug)]
+enum IndexCommands {
+    /// Chunk and embed a directory into a local index
+    Build {
+        /// Directory to index
+        dir: PathBuf,
+        /// Index name, defaults to the directory name
+        #[arg(long)]
+        name: Option<String>,
+        /// Embedding model, defaults to mistral-embed
+        #[arg(short, long)]
+        model: Option<String>,
+    },
+    /// List the local indexes
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+enum LogsCommands {
+    /// Print the entries of error.log with UTC timestamps
+    Errors {
+        /// Only entries since a duration ago (30m, 2h, 7d) or a UTC time (2026-01-31 14:00)
+        #[arg(long)]
+        since: Option<String>,
+        /// Only entries whose message matches this regex
+        #[arg(long)]
+        grep: Option<String>,
+        /// Only entries at this level or above
+        #[arg(long, value_enum)]
+        level: Option<LogLevel>,
+        /// Only entries of this run id
+        #[arg(long)]
+        run: Option<String>,
+        /// Keep printing new entries as they are written
+        #[arg(short, long)]
+        follow: bool,
+    },
+    /// Print the chunks of stream.log recorded for a run
+    Stream {
+        /// Run id to print, defaults to the last run in the log
+        #[arg(long)]
+        run: Option<String>,
+        /// Keep printing new chunks of the run as they are written
+        #[arg(short, long)]
+        follow: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum DebugCommands {
+    /// Replay a recorded stream.log through the stream parser and the TUI
+    Replay {
+        /// Stream log to replay, e.g. ~/.config/jwekke-ai-cli/stream.log
+        log: PathBuf,
+        /// Run id to replay, defaults to the last run in the log
+        #[arg(long)]
+        run: Option<String>,
+        /// Completion of the run to replay when it made several, defaults to the last
+        #[arg(long)]
+        stream: Option<usize>,
+        /// Replay without the recorded delays between chunks
+        #[arg(long)]
+        fast: bool,
+        /// Print the parsed answer to stdout without the TUI
+        #[arg(long)]
+        plain: bool,
+        /// Write a stream parser regression test for the replayed chunks to this file
+        #[arg(long, value_name = "FILE")]
+        emit_test: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum AgentsCommands {
+    /// List the agents available to the API key
+    List,
+    /// List the stored conversations, most recent first
+    History,
+}
+
+#[derive(Subcommand, Debug)]
+enum JobsCommands {
+    /// Upload a JSONL file and start a batch job on it
+    Submit {
+        /// JSONL file in the `ai batch` format or with {"custom_id", "body"} lines
+        input: PathBuf,
+        /// Model for every request of the job, fuzzy-matched against the cached model list
+        #[arg(short, long)]
+        model: Option<String>,
+        /// Wait until the job finishes
+        #[arg(long)]
+        wait: bool,
+    },
+    /// Show the status and progress of a job
+    Status {
+        id: String,
+        /// Wait until the job finishes
+        #[arg(long)]
+        wait: bool,
+    },
+    /// Download the results of a finished job
+    Results {
+        id: String,
+        /// Download the error file instead of the output file
+        #[arg(long)]
+        errors: bool,
+        /// Write the results to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Cancel a queued or running job
+    Cancel { id: String },
+}
+
+#[derive(Subcommand, Debug)]
+enum FinetuneCommands {
+    /// Validate and upload training data and create a fine-tuning job
+    Create {
+        /// JSONL file with one {"messages": [...]} conversation per line
+        #[arg(long)]
+        training_file: PathBuf,
+        /// JSONL file in the same format used to report the validation loss
+        #[arg(long)]
+        validation_file: Option<PathBuf>,
+        /// Base model to fine-tune, fuzzy-matched against the fine-tunable models
+        #[arg(short, long)]
+        model: String,
+        /// Suffix added to the fine-tuned model name
+        #[arg(long)]
+        suffix: Option<String>,
+        #[arg(long)]
+        training_steps: Option<u32>,
+        #[arg(long)]
+        learning_rate: Option<f64>,
+        #[arg(long)]
+        epochs: Option<f64>,
+        /// Create the job without starting it
+        #[arg(long)]
+        no_start: bool,
+    },
+    /// List the fine-tuning jobs
+    List,
+    /// Show the details of a job
+    Show { id: String },
+    /// Cancel a queued or running job
+    Cancel { id: String },
+}
+
+async fn resolve_or_exit(cli_model: Option<&str>, logger: &Logger) -> String {
+    match resolver::resolve_model_name(cli_model, logger).await {
+        Ok(model_name) => model_name,
+        Err(e) => {
+            eprintln!("Model name error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Applies the configured moderation guard, flagged prompts exit when it blocks.
+async fn guard_or_exit(prompt: &str, logger: &Logger) {
+    if let Err(e) = moderation::guard_prompt(prompt, logger).await {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn read_texts_or_exit(texts: &[String]) -> Vec<String> {
+    match embeddings::read_embedding_inputs(texts, &[]).await {
+        Ok(inputs) => inputs.into_iter().map(|input| input.text).collect(),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+async fn read_images_or_exit(images: &[PathBuf]) -> Vec<String> {
+    let mut image_urls = Vec::new();
+    for image in images {
+        match images::image_data_url(image).await {
+            Ok(image_url) => image_urls.push(image_url),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    }
+    image_urls
+}
+
+/// Runs one request to completion, validating the answer when JSON output is requested.
+async fn run_request(
+    request: ChatCompletionRequest,
+    registry: ToolRegistry,
+    json_output: Option<JsonOutput>,
+    sender: tokio::sync::mpsc::Sender<StreamEvent>,
+    logger: Logger,
+) -> Result<Option<serde_json::Value>, String> {
+    let result = match json_output {
+        Some(output) => {
+            structured::run_json_conversation(request, &registry, &output, sender, &logger)
+                .await
+                .map(Some)
+        }
+        None => tools::run_conversation(request, &registry, sender, &logger)
+            .await
+            .map(|_| None),
+    };
+
+    result.map_err(|e| e.to_string())
+}
+
+/// Streams every request at once and renders one pane per model.
+async fn stream_prompt(
+    logger: &Logger,
+    prompt: String,
+    requests: Vec<ChatCompletionRequest>,
+    registry: ToolRegistry,
+    json_output: Option<JsonOutput>,
+    citations: Vec<Citation>,
+    image_names: Vec<String>,
+) -> Vec<StreamPane> {
+    let mut panes = Vec::new();
+    let mut display_tasks = Vec::new();
+    for request in requests {
+        // Create a channel for real-time updates
+        let (sender, receiver) = tokio::sync::mpsc::channel(100);
+        panes.push(
+            StreamPane::new(request.model.clone(), receiver).with_citations(citations.clone()),
+        );
+
+        // Spawn a task to handle real-time updates
+        let task = run_request(
+            request,
+            registry.clone(),
+            json_output.clone(),
+            sender,
+            logger.clone(),
+        );
+        let logger_clone = logger.clone();
+        display_tasks.push(tokio::spawn(async move {
+            if let Err(e) = task.await {
+                logger_clone
+                    .log_error(format!("Completion failed: {}", e))
+                    .await;
+            }
+        }));
+    }
+
+    render_streams(logger, prompt, image_names, panes, display_tasks).await
+}
+
+/// Renders the panes in the TUI until it is closed, then stops the streams still running.
+async fn render_streams(
+    logger: &Logger,
+    prompt: String,
+    image_names: Vec<String>,
+    panes: Vec<StreamPane>,
+    display_tasks: Vec<tokio::task::JoinHandle<()>>,
+) -> Vec<StreamPane> {
+    // Initialize the terminal for the UI
+    let mut terminal = match ui::setup_terminal() {
+        Ok(terminal) => terminal,
+        Err(e) => {
+            _ = ui::restore_terminal();
+            panic!("Failed to setup terminal: {}", e);
+        }
+    };
+
+    // Render the UI
+    let panes = match ui::render_ui(&mut terminal, logger, prompt, image_names, panes).await {
+        Ok(panes) => panes,
+        Err(e) => {
+            logger.log_error(format!("{}", e)).await;
+            Vec::new()
+        }
+    };
+
+    // // Restore the terminal
+    if let Err(e) = ui::restore_terminal() {
+        eprintln!("Failed to restore terminal: {}", e);
+        std::process::exit(1);
+    }
+
+    // Streams still running after the UI closed get a moment to finish their tool calls
+    // and log writes before they are stopped
+    let deadline = tokio::time::Instant::now() + STREAM_SHUTDOWN_GRACE;
+    for (index, mut display_task) in display_tasks.into_iter().enumerate() {
+        match tokio::time::timeout_at(deadline, &mut display_task).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => logger.log_error(format!("Stream task failed: {}", e)).await,
+            Err(_) => {
+                display_task.abort();
+                let model = panes.get(index).map_or("-", |pane| pane.model.as_str());
+                logger
+                    .log_warn(format!(
+                        "Stream of {} was still running after the UI closed, aborted",
+                        model
+                    ))
+                    .await;
+            }
+        }
+    }
+
+    match markdown::print_enabled().await {
+        Ok(true) => ui::print_panes(&panes, markdown::use_styling()),
+        Ok(false) => {}
+        Err(e) => eprintln!("{}", e),
+    }
+
+    panes
+}
+
+/// Sends the prompt to an agent or continues a stored conversation.
+async fn stream_agent_prompt(
+    logger: &Logger,
+    prompt: String,
+    agent_id: Option<String>,
+    conversation_id: Option<String>,
+    image_urls: Vec<String>,
+    image_names: Vec<String>,
+    plain: bool,
+) {
+    let label = match (&agent_id, &conversation_id) {
+        (Some(agent_id), _) => format!("agent {}", agent_id),
+        (None, Some(conversation_id)) => format!("conversation {}", conversation_id),
+        (None, None) => "agent".to_string(),
+    };
+    let (sender, receiver) = tokio::sync::mpsc::channel(100);
+    let pane = StreamPane::new(label, receiver);
+    let task = tokio::spawn(agents::stream_conversation(
+        agent_id,
+        conversation_id,
+        prompt.clone(),
+        image_urls,
+        sender,
+        logger.clone(),
+    ));
+
+    if !plain {
+        let logger_clone = logger.clone();
+        let display_task = tokio::spawn(async move {
+            match task.await {
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => {
+                    logger_clone
+                        .log_error(format!("Conversation failed: {}", e))
+                        .await
+                }
+                Err(e) => {
+                    logger_clone
+                        .log_error(format!("Stream task failed: {}", e))
+                        .await
+                }
+            }
+        });
+        render_streams(logger, prompt, image_names, vec![pane], vec![display_task]).await;
+        return;
+    }
+
+    ui::render_plain(pane, true).await;
+    match task.await {
+        Ok(Ok(conversation_id)) => eprintln!(
+            "Conversation {}, continue with --conversation {}",
+            conversation_id,
+            agents::LAST_CONVERSATION
+        ),
+        Ok(Err(e)) => {
+            logger
+                .log_error(format!("Conversation failed: {}", e))
+                .await;
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        Err(e) => {
+            logger.log_error(format!("Stream task failed: {}", e)).await;
+            eprintln!("Stream task failed: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Streams a single request to stdout, JSON runs print only the validated JSON.
+async fn stream_prompt_plain(
+    logger: &Logger,
+    request: ChatCompletionRequest,
+    registry: ToolRegistry,
+    json_output: Option<JsonOutput>,
+    citations: Vec<Citation>,
+) -> StreamPane {
+    let (sender, receiver) = tokio::sync::mpsc::channel(100);
+    let pane = StreamPane::new(request.model.clone(), receiver).with_citations(citations);
+    let echo_tokens = json_output.is_none();
+
+    let task = tokio::spawn(run_request(
+        request,
+        registry,
+        json_output,
+        sender,
+        logger.clone(),
+    ));
+    let pane = ui::render_plain(pane, echo_tokens).await;
+
+    match task.await {
+        Ok(Ok(Some(value))) => println!(
+            "{}",
+            serde_json::to_string_pretty(&value).unwrap_or_else(|_| value.to_string())
+        ),
+        Ok(Ok(None)) => {}
+        Ok(Err(e)) => {
+            logger.log_error(format!("Completion failed: {}", e)).await;
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        Err(e) => {
+            logger.log_error(format!("Stream task failed: {}", e)).await;
+            eprintln!("Stream task failed: {}", e);
+            std::process::exit(1);
+        }
+    }
+
+    pane
+}
+
+async fn run_jobs_command(
+    command: &JobsCommands,
+    logger: &Logger,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        JobsCommands::Submit { input, model, wait } => {
+            file::create_config_file(Some(false)).await;
+            let model_name = resolve_or_exit(model.as_deref(), logger).await;
+
+            let contents = jobs::to_batch_api_input(
+                &tokio::fs::read_to_string(input)
+                    .await
+                    .map_err(|e| format!("Failed to read {}: {}", input.display(), e))?,
+                &model_name,
+            )?;
+            let file_name = input
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| "batch.jsonl".to_string());
+            let uploaded = client::upload_file(
+                &file_name,
+                contents.as_bytes(),
+                jobs::BATCH_FILE_PURPOSE,
+                logger,
+            )
+            .await?;
+
+            let request = BatchJobRequest {
+                input_files: vec![uploaded.id],
+                endpoint: jobs::CHAT_COMPLETIONS_ENDPOINT.to_string(),
+                model: model_name,
+                metadata: None,
+            };
+            let mut job = client::create_batch_job(&request, logger).await?;
+            println!("Submitted job {}", job.id);
+            if *wait {
+                job = jobs::wait_for_job(&job.id, logger).await?;
+                println!("{}", jobs::format_job(&job));
+            }
+        }
+        JobsCommands::Status { id, wait } => {
+            let job = if *wait {
+                jobs::wait_for_job(id, logger).await?
+            } else {
+                client::get_batch_job(id, logger).await?
+            };
+            println!("{}", jobs::format_job(&job));
+        }
+        JobsCommands::Results { id, errors, output } => {
+            let job = client::get_batch_job(id, logger).await?;
+            let file_id = if *errors {
+                job.error_file
+                    .clone()
+                    .ok_or_else(|| format!("Job {} has no error file", job.id))?
+            } else {
+                jobs::output_file_id(&job)?.to_string()
+            };
+
+            let contents = client::download_file(&file_id, logger).await?;
+            match output {
+                Some(output_path) => {
+                    tokio::fs::write(output_path, &contents).await?;
+                    println!("Results written to {}", output_path.display());
+                }
+                None => {
+                    use std::io::Write;
+                    std::io::stdout().write_all(&contents)?;
+                }
+            }
+        }
+        JobsCommands::Cancel { id } => {
+            let job = client::cancel_batch_job(id, logger).await?;
+            println!("{}", jobs::format_job(&job));
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_finetune_command(
+    command: &FinetuneCommands,
+    logger: &Logger,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        FinetuneCommands::Create {
+            training_file,
+            validation_file,
+            model,
+            suffix,
+            training_steps,
+            learning_rate,
+            epochs,
+            no_start,
+        } => {
+            let model_name = resolver::resolve_model_with_capability(
+                Some(model),
+                model,
+                ModelCapability::FineTuning,
+                logger,
+            )
+            .await?;
+
+            let training_file_id = finetune::upload_training_file(training_file, logger).await?;
+            let validation_files = match validation_file {
+                Some(path) => vec![finetune::upload_training_file(path, logger).await?],
+                None => Vec::new(),
+            };
+
+            let request = FineTuningJobRequest {
+                model: model_name,
+                training_files: vec![TrainingFile {
+                    file_id: training_file_id,
+                    weight: None,
+                }],
+                validation_files,
+                hyperparameters: FineTuningHyperparameters {
+                    training_steps: *training_steps,
+                    learning_rate: *learning_rate,
+                    epochs: *epochs,
+                },
+                suffix: suffix.clone(),
+                auto_start: !no_start,
+            };
+            let job = client::create_fine_tuning_job(&request, logger).await?;
+            println!("{}", finetune::format_job(&job));
+        }
+        FinetuneCommands::List => {
+            let jobs = client::list_fine_tuning_jobs(logger).await?;
+            if jobs.data.is_empty() {
+                println!("No fine-tuning jobs");
+            }
+            for job in &jobs.data {
+                println!("{}", finetune::format_job_row(job));
+            }
+            if jobs.total > jobs.data.len() as u64 {
+                println!("… {} more", jobs.total - jobs.data.len() as u64);
+            }
+        }
+        FinetuneCommands::Show { id } => {
+            let job = client::get_fine_tuning_job(id, logger).await?;
+            println!("{}", finetune::format_job(&job));
+        }
+        FinetuneCommands::Cancel { id } => {
+            let job = client::cancel_fine_tuning_job(id, logger).await?;
+            println!("{}", finetune::format_job(&job));
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_logs_command(command: &LogsCommands) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        LogsCommands::Errors {
+            since,
+            grep,
+            level,
+            run,
+            follow,
+        } => {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)?
+                .as_millis() as u64;
+            let filter = logs::LogFilter {
+                since: since
+                    .as_deref()
+                    .map(|since| logs::parse_since(since, now))
+                    .transpose()?,
+                grep: grep
+                    .as_deref()
+                    .map(regex::Regex::new)
+                    .transpose()
+                    .map_err(|e| format!("Invalid --grep: {}", e))?,
+                level: *level,
+                run_id: run.clone(),
+            };
+            let print_entries = |text: &str| {
+                for entry in logs::parse_error_log(text) {
+                    if filter.matches(&entry) {
+                        println!("{}", logs::format_entry(&entry));
+                    }
+                }
+            };
+
+            let path = file::get_error_log_file_path();
+            let (text, offset) = if path.exists() || !*follow {
+                logs::read_log(&path).await?
+            } else {
+                (String::new(), 0)
+            };
+            print_entries(&text);
+            if *follow {
+                logs::follow(&path, offset, print_entries).await?;
+            }
+        }
+        LogsCommands::Stream { run, follow } => {
+            let path = file::get_stream_log_file_path();
+            let (text, offset) = logs::read_log(&path).await?;
+            let chunks = replay::parse_stream_log(&text);
+            let run_id = match replay::select_run(&chunks, run.as_deref()) {
+                Ok(selected) => {
+                    for chunk in &selected {
+                        println!("{}", logs::format_chunk(chunk));
+                    }
+                    selected[0].run_id.clone()
+                }
+                // a run that has not written its first chunk yet
+                Err(_) if *follow && run.is_some() => run.clone(),
+                Err(e) => return Err(e.into()),
+            };
+
+            if *follow {
+                logs::follow(&path, offset, |text| {
+                    for chunk in replay::parse_stream_log(text) {
+                        if chunk.run_id == run_id {
+                            println!("{}", logs::format_chunk(&chunk));
+                        }
+                    }
+                })
+                .await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_debug_command(
+    command: &DebugCommands,
+    logger: &Logger,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        DebugCommands::Replay {
+            log,
+            run,
+            stream,
+            fast,
+            plain,
+            emit_test,
+        } => {
+            let text = tokio::fs::read_to_string(log)
+                .await
+                .map_err(|e| format!("Failed to read {}: {}", log.display(), e))?;
+            let run_chunks = replay::select_run(&replay::parse_stream_log(&text), run.as_deref())?;
+            let run_id = run_chunks[0]
+                .run_id
+                .clone()
+                .unwrap_or_else(|| "-".to_string());
+
+            let mut streams = replay::split_streams(run_chunks);
+            let count = streams.len();
+            let number = stream.unwrap_or(count);
+            if number == 0 || number > count {
+                return Err(format!("Run {} has {} completions", run_id, count).into());
+            }
+            let chunks = streams.swap_remove(number - 1);
+
+            let (sender, receiver) = tokio::sync::mpsc::channel(100);
+            let (tracer, mut traces) = tokio::sync::mpsc::unbounded_channel();
+            let replay_logger = logger.clone().without_stream_log();
+            let body = replay::replay_stream(chunks.clone(), *fast);
+            let task = tokio::spawn(async move {
+                let result = crate::stream::parse_mistral_stream_traced(
+                    body,
+                    sender,
+                    &replay_logger,
+                    Some(tracer),
+                )
+                .await
+                .map_err(|e| e.to_string());
+                if let Err(e) = result {
+                    replay_logger
+                        .log_error(format!("Replay failed: {}", e))
+                        .await;
+                }
+            });
+
+            let label = format!("replay {} ({}/{})", run_id, number, count);
+            let pane = StreamPane::new(label.clone(), receiver);
+            let content = if *plain {
+                let pane = ui::render_plain(pane, true).await;
+                if let Err(e) = task.await {
+                    eprintln!("Stream parser failed: {}", e);
+                }
+                pane.content
+            } else {
+                render_streams(logger, label, Vec::new(), vec![pane], vec![task])
+                    .await
+                    .into_iter()
+                    .next()
+                    .map(|pane| pane.content)
+                    .unwrap_or_default()
+            };
+
+            let mut chunk_traces = Vec::new();
+            while let Ok(trace) = traces.try_recv() {
+                chunk_traces.push(trace);
+            }
+            eprintln!(
+                "Run {}, completion {} of {}, {} chunks",
+                run_id,
+                number,
+                count,
+                chunks.len()
+            );
+            eprintln!("{}", replay::format_report(&chunks, &chunk_traces));
+
+            if let Some(path) = emit_test {
+                let test =
+                    replay::regression_test(&format!("replay_{}", run_id), &chunks, &content);
+                tokio::fs::write(path, test).await?;
+                eprintln!("Regression test written to {}", path.display());
+            }
+        }
+    }
+
+    Ok(())
 }
 
 #[tokio::main]
 async fn main() {
-    let cli = Cli::parse();
-    let logger = Logger::new().await;
+    let matches = Cli::command().get_matches();
+    let cli = match Cli::from_arg_matches(&matches) {
+        Ok(cli) => cli,
+        Err(e) => e.exit(),
+    };
+
+    let log_level = cli.log_level.or(LogLevel::from_verbosity(cli.verbose));
+    let log_options = match LogOptions::from_config(log_level, cli.log_format).await {
+        Ok(log_options) => log_options,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let logger = match Redactor::from_config().await {
+        Ok(Some(redactor)) => Logger::with_options(log_options)
+            .await
+            .with_redactor(redactor),
+        Ok(None) => Logger::with_options(log_options).await,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    logger
+        .log_info(format!(
+            "Started {} (version {})",
+            matches.subcommand_name().unwrap_or_default(),
+            env!("CARGO_PKG_VERSION")
+        ))
+        .await;
 
     match &cli.command {
         Commands::Config => match ui::select_mistral_model(&logger).await {
@@ -42,55 +1069,739 @@ async fn main() {
                     .await
             }
         },
-        Commands::Run { prompt } => {
+        Commands::Batch {
+            input,
+            output,
+            model,
+            concurrency,
+            requests_per_minute,
+        } => {
             file::create_config_file(Some(false)).await;
-            let escaped_prompt = prompt.replace("\"", "");
-            let model_name = match config::get_model_name().await {
+            let model_name = resolve_or_exit(model.as_deref(), &logger).await;
+
+            match batch::run_batch(
+                input,
+                output,
+                &model_name,
+                *concurrency,
+                *requests_per_minute,
+                &logger,
+            )
+            .await
+            {
+                Ok(summary) => println!(
+                    "{} succeeded, {} failed, {} already completed",
+                    summary.succeeded, summary.failed, summary.skipped
+                ),
+                Err(e) => {
+                    logger.log_error(format!("Batch failed: {}", e)).await;
+                    eprintln!("Batch failed: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Jobs { command } => {
+            if let Err(e) = run_jobs_command(command, &logger).await {
+                logger.log_error(format!("Batch job failed: {}", e)).await;
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Logs { command } => {
+            if let Err(e) = run_logs_command(command).await {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Debug { command } => {
+            if let Err(e) = run_debug_command(command, &logger).await {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Finetune { command } => {
+            if let Err(e) = run_finetune_command(command, &logger).await {
+                logger.log_error(format!("Fine-tuning failed: {}", e)).await;
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Fim {
+            prefix_file,
+            suffix_file,
+            marker,
+            model,
+            stop,
+            max_tokens,
+            temperature,
+        } => {
+            let model_name = match resolver::resolve_model_with_capability(
+                model.as_deref(),
+                fim::DEFAULT_FIM_MODEL,
+                ModelCapability::CompletionFim,
+                &logger,
+            )
+            .await
+            {
                 Ok(model_name) => model_name,
                 Err(e) => {
-                    _ = ui::restore_terminal();
-                    panic!("Model name error: {}", e);
+                    eprintln!("Model name error: {}", e);
+                    std::process::exit(1);
                 }
             };
 
-            // Initialize the terminal for the UI
-            let mut terminal = match ui::setup_terminal() {
-                Ok(terminal) => terminal,
+            let (prefix, suffix) =
+                match fim::read_fim_input(prefix_file.as_ref(), suffix_file.as_ref(), marker).await
+                {
+                    Ok(input) => input,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        std::process::exit(1);
+                    }
+                };
+
+            let request = FimCompletionRequest {
+                model: model_name,
+                prompt: prefix,
+                suffix,
+                stream: true,
+                stop: stop.clone(),
+                max_tokens: *max_tokens,
+                temperature: *temperature,
+            };
+            if let Err(e) = fim::run_fim(request, &logger).await {
+                eprintln!("FIM completion failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Ocr {
+            input,
+            model,
+            format,
+            output,
+            run,
+            plain,
+        } => {
+            file::create_config_file(Some(false)).await;
+            let model_name = match resolver::resolve_model_with_capability(
+                model.as_deref(),
+                ocr::DEFAULT_OCR_MODEL,
+                ModelCapability::Ocr,
+                &logger,
+            )
+            .await
+            {
+                Ok(model_name) => model_name,
                 Err(e) => {
-                    _ = ui::restore_terminal();
-                    panic!("Failed to setup terminal: {}", e);
+                    eprintln!("Model name error: {}", e);
+                    std::process::exit(1);
                 }
             };
 
-            // Create a channel for real-time updates
-            let (sender, receiver) = tokio::sync::mpsc::channel(100);
+            let document = match ocr::ocr_document(input).await {
+                Ok(document) => document,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            let request = OcrRequest {
+                model: model_name,
+                document,
+            };
+            let response = match client::call_mistral_ocr(&request, &logger).await {
+                Ok(response) => response,
+                Err(e) => {
+                    eprintln!("OCR failed: {}", e);
+                    std::process::exit(1);
+                }
+            };
 
-            // Spawn a task to handle real-time updates
-            let prompt_clone = escaped_prompt.clone();
-            let logger_clone = logger.clone();
-            let display_task = tokio::spawn(async move {
-                if let Err(_) =
-                    client::call_mistral_completions(prompt_clone, sender, &logger_clone).await
-                {
-                    // TODO: Handle error
+            let text = match ocr::format_response(&response, *format) {
+                Ok(text) => text,
+                Err(e) => {
+                    eprintln!("Failed to format the OCR result: {}", e);
+                    std::process::exit(1);
                 }
-            });
+            };
+            match output {
+                Some(output_path) => {
+                    if let Err(e) = tokio::fs::write(output_path, &text).await {
+                        eprintln!("Failed to write {}: {}", output_path.display(), e);
+                        std::process::exit(1);
+                    }
+                }
+                None if run.is_none() => println!("{}", text),
+                None => eprintln!("Recognized {} pages of {}", response.pages.len(), input),
+            }
+
+            if let Some(run_prompt) = run {
+                let escaped_prompt = run_prompt.replace("\"", "");
+                let model_name = resolve_or_exit(None, &logger).await;
+                let follow_up_prompt = ocr::follow_up_prompt(&escaped_prompt, input, &response);
+                guard_or_exit(&follow_up_prompt, &logger).await;
+                let request = ChatCompletionRequest::new(
+                    model_name,
+                    vec![ChatMessage::user(follow_up_prompt)],
+                );
+
+                if *plain {
+                    stream_prompt_plain(
+                        &logger,
+                        request,
+                        ToolRegistry::default(),
+                        None,
+                        Vec::new(),
+                    )
+                    .await;
+                } else {
+                    stream_prompt(
+                        &logger,
+                        escaped_prompt,
+                        vec![request],
+                        ToolRegistry::default(),
+                        None,
+                        Vec::new(),
+                        Vec::new(),
+                    )
+                    .await;
+                }
+            }
+        }
+        Commands::Transcribe {
+            file,
+            model,
+            language,
+            format,
+            timestamps,
+            output,
+        } => {
+            let model_name = match resolver::resolve_model_with_capability(
+                model.as_deref(),
+                transcribe::DEFAULT_TRANSCRIPTION_MODEL,
+                ModelCapability::Audio,
+                &logger,
+            )
+            .await
+            {
+                Ok(model_name) => model_name,
+                Err(e) => {
+                    eprintln!("Model name error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            let segment_timestamps = *timestamps || *format != TranscriptFormat::Text;
+            let request = match transcribe::read_audio_request(
+                file,
+                model_name,
+                language.clone(),
+                segment_timestamps,
+            )
+            .await
+            {
+                Ok(request) => request,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            let transcript = match client::call_mistral_transcription(&request, &logger).await {
+                Ok(response) => transcribe::format_transcript(&response, *format),
+                Err(e) => {
+                    eprintln!("Transcription failed: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            let transcript = match transcript {
+                Ok(transcript) => transcript,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            match output {
+                Some(output_path) => {
+                    if let Err(e) = tokio::fs::write(output_path, &transcript).await {
+                        eprintln!("Failed to write {}: {}", output_path.display(), e);
+                        std::process::exit(1);
+                    }
+                }
+                None => println!("{}", transcript),
+            }
+        }
+        Commands::Moderate { texts, model } => {
+            let model_name = match resolver::resolve_model_with_capability(
+                model.as_deref(),
+                moderation::DEFAULT_MODERATION_MODEL,
+                ModelCapability::Moderation,
+                &logger,
+            )
+            .await
+            {
+                Ok(model_name) => model_name,
+                Err(e) => {
+                    eprintln!("Model name error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            let request = ModerationRequest {
+                model: model_name,
+                input: read_texts_or_exit(texts).await,
+            };
+            match client::call_mistral_moderation(&request, &logger).await {
+                Ok(response) => {
+                    for (index, result) in response.results.iter().enumerate() {
+                        if response.results.len() > 1 {
+                            println!("Input {}:", index + 1);
+                        }
+                        println!("{}", moderation::format_scores(result));
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Moderation failed: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Classify { texts, model } => {
+            let model_name = match resolver::resolve_model_with_capability(
+                Some(model),
+                model,
+                ModelCapability::Classification,
+                &logger,
+            )
+            .await
+            {
+                Ok(model_name) => model_name,
+                Err(e) => {
+                    eprintln!("Model name error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            let request = ModerationRequest {
+                model: model_name,
+                input: read_texts_or_exit(texts).await,
+            };
+            match client::call_mistral_classification(&request, &logger).await {
+                Ok(response) => {
+                    for (index, result) in response.results.iter().enumerate() {
+                        if response.results.len() > 1 {
+                            println!("Input {}:", index + 1);
+                        }
+                        for (target, classification) in result {
+                            let mut scores = classification
+                                .scores
+                                .iter()
+                                .collect::<Vec<(&String, &f64)>>();
+                            scores.sort_by(|a, b| b.1.total_cmp(a.1));
+                            let scores = scores
+                                .into_iter()
+                                .map(|(label, score)| format!("{} {:.4}", label, score))
+                                .collect::<Vec<String>>();
+                            println!("{}: {}", target, scores.join(", "));
+                        }
+                    }
+                }
+                Err(e) => {
+                    eprintln!("Classification failed: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Embed {
+            texts,
+            files,
+            model,
+            format,
+            output,
+        } => {
+            let model_name = model
+                .clone()
+                .unwrap_or_else(|| client::DEFAULT_EMBEDDING_MODEL.to_string());
+
+            let inputs = match embeddings::read_embedding_inputs(texts, files).await {
+                Ok(inputs) => inputs,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+            let input_texts = inputs
+                .iter()
+                .map(|input| input.text.clone())
+                .collect::<Vec<String>>();
+
+            let vectors = match embeddings::embed_texts(
+                &input_texts,
+                &model_name,
+                &logger,
+                |_, _| {},
+            )
+            .await
+            {
+                Ok(vectors) => vectors,
+                Err(e) => {
+                    eprintln!("Embedding failed: {}", e);
+                    std::process::exit(1);
+                }
+            };
 
-            // Render the UI
             if let Err(e) =
-                ui::render_ui(&mut terminal, &logger, model_name, escaped_prompt, receiver).await
+                embeddings::write_embeddings(output.as_deref(), *format, &inputs, &vectors)
             {
-                logger.log_error(format!("{}", e)).await;
+                eprintln!("Failed to write embeddings: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Commands::Agents {
+            command: AgentsCommands::List,
+        } => match client::list_agents(&logger).await {
+            Ok(agents) if agents.is_empty() => println!("No agents"),
+            Ok(agents) => {
+                for agent in &agents {
+                    println!("{}", agents::format_agent_row(agent));
+                }
+            }
+            Err(e) => {
+                eprintln!("Failed to list agents: {}", e);
+                std::process::exit(1);
+            }
+        },
+        Commands::Agents {
+            command: AgentsCommands::History,
+        } => {
+            let history = agents::read_history().await;
+            if history.is_empty() {
+                println!("No stored conversations");
             }
+            for record in &history {
+                println!("{}", agents::format_history_row(record));
+            }
+        }
+        Commands::Index {
+            command: IndexCommands::Build { dir, name, model },
+        } => {
+            let index_name = name
+                .clone()
+                .unwrap_or_else(|| index::default_index_name(dir));
+            let model_name = model
+                .clone()
+                .unwrap_or_else(|| client::DEFAULT_EMBEDDING_MODEL.to_string());
 
-            // Wait for the display task to complete
-            display_task.await.unwrap();
+            match index::build_index(dir, &index_name, &model_name, &logger).await {
+                Ok(meta) => println!(
+                    "Indexed {} chunks of {} as {}",
+                    meta.chunk_count,
+                    meta.root.display(),
+                    meta.name
+                ),
+                Err(e) => {
+                    logger.log_error(format!("Index build failed: {}", e)).await;
+                    eprintln!("Index build failed: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Index {
+            command: IndexCommands::List,
+        } => {
+            let indexes = index::list_indexes();
+            if indexes.is_empty() {
+                println!("No indexes yet, build one with `ai index build <dir>`");
+            }
+            for meta in indexes {
+                println!(
+                    "{} ({} chunks, {}) {}",
+                    meta.name,
+                    meta.chunk_count,
+                    meta.model,
+                    meta.root.display()
+                );
+            }
+        }
+        Commands::Mcp => {
+            file::create_config_file(Some(false)).await;
+            let servers = mcp::get_server_configs().await;
+            if servers.is_empty() {
+                println!(
+                    "No MCP servers configured, add MCP_SERVER=\"name=command args\" to the config"
+                );
+            }
 
-            // // Restore the terminal
-            if let Err(e) = ui::restore_terminal() {
-                eprintln!("Failed to restore terminal: {}", e);
+            let allow_list = mcp::get_allow_list().await;
+            for server in servers {
+                println!(
+                    "{} ({} {})",
+                    server.name,
+                    server.command,
+                    server.args.join(" ")
+                );
+                let tools = match mcp::McpClient::spawn(&server).await {
+                    Ok(client) => client.list_tools().await,
+                    Err(e) => Err(e),
+                };
+                match tools {
+                    Ok(tools) => {
+                        for tool in tools {
+                            let approval = if allow_list.allows(&server.name, &tool.name) {
+                                "allowed"
+                            } else {
+                                "needs approval"
+                            };
+                            println!(
+                                "  {} [{}] {}",
+                                tool.name,
+                                approval,
+                                tool.description.unwrap_or_default()
+                            );
+                        }
+                    }
+                    Err(e) => println!("  unavailable: {}", e),
+                }
+            }
+        }
+        Commands::Run {
+            prompt,
+            model,
+            tools,
+            json,
+            schema,
+            repair,
+            plain,
+            images,
+            agent,
+            conversation,
+        } => {
+            file::create_config_file(Some(false)).await;
+            let escaped_prompt = prompt.replace("\"", "");
+
+            if agent.is_some() || conversation.is_some() {
+                let conversation_id = match conversation {
+                    Some(conversation) => {
+                        let history = agents::read_history().await;
+                        match agents::resolve_conversation_id(&history, conversation) {
+                            Ok(conversation_id) => Some(conversation_id),
+                            Err(e) => {
+                                eprintln!("{}", e);
+                                std::process::exit(1);
+                            }
+                        }
+                    }
+                    None => None,
+                };
+                guard_or_exit(&escaped_prompt, &logger).await;
+                let image_urls = read_images_or_exit(images).await;
+
+                stream_agent_prompt(
+                    &logger,
+                    escaped_prompt,
+                    agent.clone(),
+                    conversation_id,
+                    image_urls,
+                    images::image_names(images),
+                    *plain,
+                )
+                .await;
+                return;
+            }
+
+            let model_name = resolve_or_exit(model.as_deref(), &logger).await;
+
+            if !images.is_empty() {
+                match resolver::find_model_card(&model_name, &logger).await {
+                    Some(card) if !card.capabilities.vision => {
+                        eprintln!("Model {} does not support image input", model_name);
+                        std::process::exit(1);
+                    }
+                    Some(_) => {}
+                    None => {
+                        let message = format!(
+                            "Model {} has unknown capabilities, sending the images anyway",
+                            model_name
+                        );
+                        eprintln!("{}", message);
+                        logger.log_warn(message).await;
+                    }
+                }
+            }
+
+            guard_or_exit(&escaped_prompt, &logger).await;
+
+            let image_urls = read_images_or_exit(images).await;
+
+            let registry = if *tools {
+                if let Some(card) = resolver::find_model_card(&model_name, &logger).await
+                    && !card.capabilities.function_calling
+                {
+                    eprintln!("Model {} does not support function calling", model_name);
+                    std::process::exit(1);
+                }
+                ToolRegistry::builtin().with_mcp_servers(&logger).await
+            } else {
+                ToolRegistry::default()
+            };
+
+            let json_output = match schema {
+                Some(schema_path) => match structured::read_schema_file(schema_path).await {
+                    Ok(schema) => Some(JsonOutput {
+                        schema: Some(schema),
+                        repair: *repair,
+                    }),
+                    Err(e) => {
+                        eprintln!("Failed to load schema {}: {}", schema_path.display(), e);
+                        std::process::exit(1);
+                    }
+                },
+                None if *json => Some(JsonOutput {
+                    schema: None,
+                    repair: *repair,
+                }),
+                None => None,
+            };
+
+            let mut request = ChatCompletionRequest::new(
+                model_name,
+                vec![ChatMessage::user_with_images(
+                    escaped_prompt.clone(),
+                    image_urls,
+                )],
+            );
+            if let Some(output) = &json_output {
+                let schema_name = schema
+                    .as_ref()
+                    .and_then(|path| path.file_stem())
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or_else(|| "response".to_string());
+                request.response_format = Some(output.response_format(&schema_name));
+            }
+
+            if *plain {
+                stream_prompt_plain(&logger, request, registry, json_output, Vec::new()).await;
+            } else {
+                stream_prompt(
+                    &logger,
+                    escaped_prompt,
+                    vec![request],
+                    registry,
+                    json_output,
+                    Vec::new(),
+                    images::image_names(images),
+                )
+                .await;
+            }
+        }
+        Commands::Ask {
+            question,
+            index,
+            top_k,
+            model,
+            plain,
+        } => {
+            file::create_config_file(Some(false)).await;
+            let escaped_question = question.replace("\"", "");
+            let model_name = resolve_or_exit(model.as_deref(), &logger).await;
+            guard_or_exit(&escaped_question, &logger).await;
+
+            let local_index = match LocalIndex::load(index) {
+                Ok(local_index) => local_index,
+                Err(e) => {
+                    eprintln!("Failed to load index {}: {}", index, e);
+                    std::process::exit(1);
+                }
+            };
+
+            // the question has to be embedded with the model the index was built with
+            let query = match embeddings::embed_texts(
+                std::slice::from_ref(&escaped_question),
+                &local_index.meta.model,
+                &logger,
+                |_, _| {},
+            )
+            .await
+            {
+                Ok(mut vectors) => vectors.remove(0),
+                Err(e) => {
+                    eprintln!("Failed to embed the question: {}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            let chunks = local_index.search(&query, *top_k);
+            let (messages, citations) =
+                index::retrieval_messages(&escaped_question, &chunks, &local_index.meta.root);
+            let request = ChatCompletionRequest::new(model_name, messages);
+
+            if *plain {
+                stream_prompt_plain(&logger, request, ToolRegistry::default(), None, citations)
+                    .await;
+            } else {
+                stream_prompt(
+                    &logger,
+                    escaped_question,
+                    vec![request],
+                    ToolRegistry::default(),
+                    None,
+                    citations,
+                    Vec::new(),
+                )
+                .await;
+            }
+        }
+        Commands::Compare {
+            prompt,
+            models,
+            export,
+        } => {
+            if models.len() < 2 {
+                eprintln!("Compare needs at least two models, e.g. -m tiny -m medium");
                 std::process::exit(1);
             }
+
+            file::create_config_file(Some(false)).await;
+            let escaped_prompt = prompt.replace("\"", "");
+
+            let mut model_names = Vec::new();
+            for model in models {
+                model_names.push(resolve_or_exit(Some(model), &logger).await);
+            }
+            guard_or_exit(&escaped_prompt, &logger).await;
+
+            let requests = model_names
+                .into_iter()
+                .map(|model_name| {
+                    ChatCompletionRequest::new(
+                        model_name,
+                        vec![ChatMessage::user(escaped_prompt.clone())],
+                    )
+                })
+                .collect();
+            let panes = stream_prompt(
+                &logger,
+                escaped_prompt.clone(),
+                requests,
+                ToolRegistry::default(),
+                None,
+                Vec::new(),
+                Vec::new(),
+            )
+            .await;
+
+            if let Some(export_path) = export {
+                match export::write_markdown_export(export_path, &escaped_prompt, &panes).await {
+                    Ok(_) => println!("Comparison exported to {}", export_path.display()),
+                    Err(e) => {
+                        logger
+                            .log_error(format!("Failed to export comparison: {}", e))
+                            .await;
+                        eprintln!("Failed to export comparison: {}", e);
+                    }
+                }
+            }
         }
     }
 }
@@ -0,0 +1,176 @@
+use crate::config;
+use regex::Regex;
+use std::{
+    env,
+    io::{self, IsTerminal},
+};
+
+const PRINT_CONFIG_KEY: &str = "PRINT_RESPONSE";
+const RULE_WIDTH: usize = 40;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const ITALIC: &str = "\x1b[3m";
+const UNDERLINE: &str = "\x1b[4m";
+const CODE: &str = "\x1b[36m";
+const HEADING: &str = "\x1b[1;35m";
+
+pub fn parse_print_option(value: Option<&str>) -> Result<bool, String> {
+    match value.map(|value| value.trim().to_lowercase()).as_deref() {
+        None | Some("") | Some("on") | Some("true") => Ok(true),
+        Some("off") | Some("false") => Ok(false),
+        Some(other) => Err(format!(
+            "Invalid {} \"{}\", use on or off",
+            PRINT_CONFIG_KEY, other
+        )),
+    }
+}
+
+/// Whether the answer is printed to the terminal after the TUI closes, on unless
+/// `PRINT_RESPONSE="off"`.
+pub async fn print_enabled() -> Result<bool, String> {
+    parse_print_option(config::get_config_value(PRINT_CONFIG_KEY).await.as_deref())
+}
+
+/// ANSI styling only goes to a terminal, and not when `NO_COLOR` is set.
+pub fn use_styling() -> bool {
+    io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none()
+}
+
+struct InlineRules {
+    link: Regex,
+    bold: Regex,
+    italic: Regex,
+}
+
+impl InlineRules {
+    fn new() -> Self {
+        InlineRules {
+            link: Regex::new(r"\[([^\]]+)\]\(([^)\s]+)\)").unwrap(),
+            bold: Regex::new(r"\*\*([^*]+)\*\*|__([^_]+)__").unwrap(),
+            // underscores are left alone, they are more often snake_case than emphasis
+            italic: Regex::new(r"\*([^*\s][^*]*)\*").unwrap(),
+        }
+    }
+
+    fn style(&self, text: &str) -> String {
+        let text = self
+            .link
+            .replace_all(text, format!("{UNDERLINE}$1{RESET} {DIM}($2){RESET}"));
+        let text = self.bold.replace_all(&text, format!("{BOLD}$1$2{RESET}"));
+        self.italic
+            .replace_all(&text, format!("{ITALIC}$1{RESET}"))
+            .into_owned()
+    }
+
+    /// Styles a line outside code blocks, `inline code` is kept as written.
+    fn line(&self, line: &str) -> String {
+        let parts = line.split('`').collect::<Vec<&str>>();
+        // an unmatched backtick is plain text
+        let paired = parts.len() % 2 == 1;
+        parts
+            .iter()
+            .enumerate()
+            .map(|(index, part)| {
+                if index % 2 == 1 && (paired || index + 1 < parts.len()) {
+                    format!("{CODE}{}{RESET}", part)
+                } else if index % 2 == 1 {
+                    format!("`{}", self.style(part))
+                } else {
+                    self.style(part)
+                }
+            })
+            .collect()
+    }
+}
+
+/// Renders the markdown of an answer with ANSI styles for the terminal scrollback:
+/// headings, emphasis, inline code, code blocks, lists, quotes, links and rules.
+pub fn to_ansi(text: &str) -> String {
+    let rules = InlineRules::new();
+    let mut in_code = false;
+    let mut output = String::new();
+
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        let indent = &line[..line.len() - trimmed.len()];
+
+        let rendered = if let Some(language) = trimmed.strip_prefix("```") {
+            in_code = !in_code;
+            format!("{DIM}{}```{}{RESET}", indent, language)
+        } else if in_code {
+            format!("{CODE}{}{RESET}", line)
+        } else if let Some((hashes, heading)) = trimmed.split_once(' ')
+            && !hashes.is_empty()
+            && hashes.len() <= 6
+            && hashes.chars().all(|c| c == '#')
+        {
+            let style = if hashes.len() == 1 {
+                format!("{HEADING}{UNDERLINE}")
+            } else {
+                HEADING.to_string()
+            };
+            format!("{}{}{}", style, heading.trim(), RESET)
+        } else if ["---", "***", "___"].contains(&trimmed.trim_end()) {
+            format!("{DIM}{}{RESET}", "─".repeat(RULE_WIDTH))
+        } else if let Some(quote) = trimmed.strip_prefix('>') {
+            format!("{DIM}│{RESET} {}", rules.line(quote.trim_start()))
+        } else if let Some(item) = trimmed
+            .strip_prefix("- ")
+            .or_else(|| trimmed.strip_prefix("* "))
+            .or_else(|| trimmed.strip_prefix("+ "))
+        {
+            format!("{}• {}", indent, rules.line(item))
+        } else {
+            rules.line(line)
+        };
+
+        output.push_str(&rendered);
+        output.push('\n');
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn block_elements_are_styled() {
+        let text =
+            "# Title\n## Part\n- one\n  * two\n> quoted\n---\n```rust\nlet **a** = 1;\n```\nplain";
+
+        assert_eq!(
+            to_ansi(text),
+            "\x1b[1;35m\x1b[4mTitle\x1b[0m\n\
+             \x1b[1;35mPart\x1b[0m\n\
+             • one\n  \
+             • two\n\
+             \x1b[2m│\x1b[0m quoted\n\
+             \x1b[2m────────────────────────────────────────\x1b[0m\n\
+             \x1b[2m```rust\x1b[0m\n\
+             \x1b[36mlet **a** = 1;\x1b[0m\n\
+             \x1b[2m```\x1b[0m\n\
+             plain\n"
+        );
+    }
+
+    #[test]
+    fn inline_styles_skip_code_spans() {
+        assert_eq!(
+            to_ansi("**Bold** and *it* with `**raw**` in snake_case_name, see [docs](https://x.y)"),
+            "\x1b[1mBold\x1b[0m and \x1b[3mit\x1b[0m with \x1b[36m**raw**\x1b[0m in snake_case_name, \
+             see \x1b[4mdocs\x1b[0m \x1b[2m(https://x.y)\x1b[0m\n"
+        );
+        assert_eq!(to_ansi("a ` b"), "a ` b\n");
+    }
+
+    #[test]
+    fn print_option_defaults_to_on() {
+        assert_eq!(parse_print_option(None), Ok(true));
+        assert_eq!(parse_print_option(Some("Off")), Ok(false));
+        assert!(parse_print_option(Some("maybe")).is_err());
+    }
+}
@@ -0,0 +1,522 @@
+use crate::{config, logger::Logger};
+use serde::Deserialize;
+use std::{
+    process::Stdio,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    process::{Child, Command},
+    sync::Mutex,
+};
+
+const MCP_SERVER_CONFIG_KEY: &str = "MCP_SERVER";
+const MCP_ALLOW_CONFIG_KEY: &str = "MCP_ALLOW";
+const MCP_TIMEOUT_CONFIG_KEY: &str = "MCP_TIMEOUT";
+const MCP_PROTOCOL_VERSION: &str = "2024-11-05";
+const DEFAULT_MCP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// An MCP server launched over stdio, configured as `MCP_SERVER="name=command args..."`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct McpServerConfig {
+    pub name: String,
+    pub command: String,
+    pub args: Vec<String>,
+}
+
+impl McpServerConfig {
+    pub fn parse(value: &str) -> Option<Self> {
+        let (name, command_line) = value.split_once('=')?;
+        let mut parts = command_line.split_whitespace().map(str::to_string);
+        let command = parts.next()?;
+
+        Some(McpServerConfig {
+            name: name.trim().to_string(),
+            command,
+            args: parts.collect(),
+        })
+    }
+}
+
+/// Tools that run without asking, configured as `MCP_ALLOW="server/tool"` or `"server/*"`.
+#[derive(Debug, Clone, Default)]
+pub struct McpAllowList {
+    rules: Vec<(String, String)>,
+}
+
+impl McpAllowList {
+    pub fn new(rules: &[String]) -> Self {
+        McpAllowList {
+            rules: rules
+                .iter()
+                .filter_map(|rule| rule.split_once('/'))
+                .map(|(server, tool)| (server.trim().to_string(), tool.trim().to_string()))
+                .collect(),
+        }
+    }
+
+    pub fn allows(&self, server: &str, tool: &str) -> bool {
+        self.rules.iter().any(|(rule_server, rule_tool)| {
+            rule_server == server && (rule_tool == "*" || rule_tool == tool)
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct McpTool {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(rename = "inputSchema")]
+    pub input_schema: serde_json::Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct McpToolList {
+    tools: Vec<McpTool>,
+    #[serde(rename = "nextCursor")]
+    next_cursor: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct McpContent {
+    r#type: String,
+    text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct McpToolResult {
+    content: Vec<McpContent>,
+    #[serde(rename = "isError", default)]
+    is_error: bool,
+}
+
+struct McpIo {
+    reader: BufReader<Box<dyn AsyncRead + Send + Unpin>>,
+    writer: Box<dyn AsyncWrite + Send + Unpin>,
+}
+
+/// JSON-RPC client for a single MCP server, requests are sent one at a time.
+pub struct McpClient {
+    pub name: String,
+    io: Mutex<McpIo>,
+    next_id: AtomicU64,
+    timeout: Duration,
+    _child: Option<Child>,
+}
+
+impl std::fmt::Debug for McpClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("McpClient")
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+impl McpClient {
+    /// Launches the configured server process and performs the initialize handshake.
+    pub async fn spawn(config: &McpServerConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut child = Command::new(&config.command)
+            .args(&config.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| format!("Failed to launch MCP server {}: {}", config.name, e))?;
+
+        let stdin = child.stdin.take().ok_or("MCP server stdin unavailable")?;
+        let stdout = child.stdout.take().ok_or("MCP server stdout unavailable")?;
+
+        let mut client = Self::connect(
+            config.name.clone(),
+            Box::new(stdout),
+            Box::new(stdin),
+            get_timeout().await,
+        )
+        .await?;
+        client._child = Some(child);
+        Ok(client)
+    }
+
+    /// Wraps an already connected transport and performs the initialize handshake,
+    /// every request fails when the server takes longer than `timeout` to answer.
+    pub async fn connect(
+        name: String,
+        reader: Box<dyn AsyncRead + Send + Unpin>,
+        writer: Box<dyn AsyncWrite + Send + Unpin>,
+        timeout: Duration,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let client = McpClient {
+            name,
+            io: Mutex::new(McpIo {
+                reader: BufReader::new(reader),
+                writer,
+            }),
+            next_id: AtomicU64::new(1),
+            timeout,
+            _child: None,
+        };
+
+        client
+            .request(
+                "initialize",
+                serde_json::json!({
+                    "protocolVersion": MCP_PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": {
+                        "name": env!("CARGO_PKG_NAME"),
+                        "version": env!("CARGO_PKG_VERSION")
+                    }
+                }),
+            )
+            .await?;
+        client
+            .notify("notifications/initialized", serde_json::json!({}))
+            .await?;
+
+        Ok(client)
+    }
+
+    async fn write_message(
+        io: &mut McpIo,
+        message: serde_json::Value,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut line = serde_json::to_vec(&message)?;
+        line.push(b'\n');
+        io.writer.write_all(&line).await?;
+        io.writer.flush().await?;
+        Ok(())
+    }
+
+    async fn notify(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut io = self.io.lock().await;
+        Self::write_message(
+            &mut io,
+            serde_json::json!({"jsonrpc": "2.0", "method": method, "params": params}),
+        )
+        .await
+    }
+
+    async fn request(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let mut io = self.io.lock().await;
+        Self::write_message(
+            &mut io,
+            serde_json::json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}),
+        )
+        .await?;
+
+        // a late answer is skipped by the next request, its id doesn't match
+        let response = async {
+            loop {
+                let mut line = String::new();
+                if io.reader.read_line(&mut line).await? == 0 {
+                    return Err(format!("MCP server {} closed the connection", self.name).into());
+                }
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                let message = serde_json::from_str::<serde_json::Value>(&line)?;
+
+                // server initiated requests aren't supported, notifications are ignored
+                if let Some(server_method) = message.get("method") {
+                    if let Some(server_request_id) = message.get("id") {
+                        Self::write_message(
+                            &mut io,
+                            serde_json::json!({
+                                "jsonrpc": "2.0",
+                                "id": server_request_id,
+                                "error": {"code": -32601, "message": format!("Method not supported: {}", server_method)}
+                            }),
+                        )
+                        .await?;
+                    }
+                    continue;
+                }
+
+                if message.get("id").and_then(|value| value.as_u64()) != Some(id) {
+                    continue;
+                }
+
+                if let Some(error) = message.get("error") {
+                    return Err(format!("MCP server {} error: {}", self.name, error).into());
+                }
+
+                return Ok(message
+                    .get("result")
+                    .cloned()
+                    .unwrap_or(serde_json::Value::Null));
+            }
+        };
+        match tokio::time::timeout(self.timeout, response).await {
+            Ok(result) => result,
+            Err(_) => Err(format!("MCP server {} did not answer {}", self.name, method).into()),
+        }
+    }
+
+    pub async fn list_tools(&self) -> Result<Vec<McpTool>, Box<dyn std::error::Error>> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let params = match &cursor {
+                Some(cursor) => serde_json::json!({"cursor": cursor}),
+                None => serde_json::json!({}),
+            };
+            let page =
+                serde_json::from_value::<McpToolList>(self.request("tools/list", params).await?)?;
+            tools.extend(page.tools);
+
+            match page.next_cursor {
+                Some(next_cursor) => cursor = Some(next_cursor),
+                None => return Ok(tools),
+            }
+        }
+    }
+
+    /// Calls a tool and flattens its text content, tool level errors become `Err`.
+    pub async fn call_tool(
+        &self,
+        name: &str,
+        arguments: serde_json::Value,
+    ) -> Result<Result<String, String>, Box<dyn std::error::Error>> {
+        let result = serde_json::from_value::<McpToolResult>(
+            self.request(
+                "tools/call",
+                serde_json::json!({"name": name, "arguments": arguments}),
+            )
+            .await?,
+        )?;
+
+        let text = result
+            .content
+            .iter()
+            .map(|content| match &content.text {
+                Some(text) => text.clone(),
+                None => format!("[{} content]", content.r#type),
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        Ok(if result.is_error { Err(text) } else { Ok(text) })
+    }
+}
+
+pub async fn get_allow_list() -> McpAllowList {
+    McpAllowList::new(
+        &config::get_config_values(MCP_ALLOW_CONFIG_KEY)
+            .await
+            .unwrap_or_default(),
+    )
+}
+
+/// Seconds to wait for an MCP server's answer, `MCP_TIMEOUT` in the config.
+pub async fn get_timeout() -> Duration {
+    config::get_config_value(MCP_TIMEOUT_CONFIG_KEY)
+        .await
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .filter(|seconds| *seconds > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_MCP_TIMEOUT)
+}
+
+pub async fn get_server_configs() -> Vec<McpServerConfig> {
+    config::get_config_values(MCP_SERVER_CONFIG_KEY)
+        .await
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|value| McpServerConfig::parse(value))
+        .collect()
+}
+
+/// Launches every configured MCP server, servers failing to start are logged and skipped.
+pub async fn connect_configured_servers(logger: &Logger) -> Vec<Arc<McpClient>> {
+    let mut clients = Vec::new();
+
+    for server in get_server_configs().await {
+        match McpClient::spawn(&server).await {
+            Ok(client) => clients.push(Arc::new(client)),
+            Err(e) => {
+                logger
+                    .log_error(format!("MCP server {} unavailable: {}", server.name, e))
+                    .await
+            }
+        }
+    }
+
+    clients
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use tokio::io::{DuplexStream, ReadHalf, WriteHalf};
+
+    async fn respond(writer: &mut WriteHalf<DuplexStream>, message: serde_json::Value) {
+        let mut line = serde_json::to_vec(&message).unwrap();
+        line.push(b'\n');
+        writer.write_all(&line).await.unwrap();
+    }
+
+    async fn run_fake_server(reader: ReadHalf<DuplexStream>, mut writer: WriteHalf<DuplexStream>) {
+        let mut lines = BufReader::new(reader).lines();
+
+        while let Ok(Some(line)) = lines.next_line().await {
+            let request = serde_json::from_str::<serde_json::Value>(&line).unwrap();
+            let id = request.get("id").cloned();
+            let result = match request["method"].as_str().unwrap() {
+                "initialize" => serde_json::json!({
+                    "protocolVersion": MCP_PROTOCOL_VERSION,
+                    "capabilities": {"tools": {}},
+                    "serverInfo": {"name": "fake", "version": "0.0.1"}
+                }),
+                "tools/list" => serde_json::json!({
+                    "tools": [
+                        {
+                            "name": "echo",
+                            "description": "Echo the given text",
+                            "inputSchema": {
+                                "type": "object",
+                                "properties": {"text": {"type": "string"}},
+                                "required": ["text"]
+                            }
+                        },
+                        {
+                            "name": "fail",
+                            "inputSchema": {"type": "object", "properties": {}}
+                        }
+                    ]
+                }),
+                "tools/call" if request["params"]["name"] == "echo" => {
+                    // a log notification in between must not be taken as the response
+                    respond(
+                        &mut writer,
+                        serde_json::json!({"jsonrpc": "2.0", "method": "notifications/message", "params": {"level": "info", "data": "echoing"}}),
+                    )
+                    .await;
+                    serde_json::json!({
+                        "content": [{"type": "text", "text": request["params"]["arguments"]["text"]}]
+                    })
+                }
+                "tools/call" => serde_json::json!({
+                    "content": [{"type": "text", "text": "tool failed"}],
+                    "isError": true
+                }),
+                _ => continue,
+            };
+
+            if let Some(id) = id {
+                respond(
+                    &mut writer,
+                    serde_json::json!({"jsonrpc": "2.0", "id": id, "result": result}),
+                )
+                .await;
+            }
+        }
+    }
+
+    /// Connects a client to an in-process fake MCP server exposing `echo` and `fail` tools.
+    pub async fn fake_mcp_client(name: &str) -> McpClient {
+        let (client_stream, server_stream) = tokio::io::duplex(64 * 1024);
+        let (server_reader, server_writer) = tokio::io::split(server_stream);
+        tokio::spawn(run_fake_server(server_reader, server_writer));
+
+        let (client_reader, client_writer) = tokio::io::split(client_stream);
+        McpClient::connect(
+            name.to_string(),
+            Box::new(client_reader),
+            Box::new(client_writer),
+            DEFAULT_MCP_TIMEOUT,
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn silent_server_times_out() {
+        let (client_stream, server_stream) = tokio::io::duplex(64 * 1024);
+        // reads the requests and never answers
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(server_stream).lines();
+            while let Ok(Some(_)) = lines.next_line().await {}
+        });
+
+        let (client_reader, client_writer) = tokio::io::split(client_stream);
+        let error = McpClient::connect(
+            "silent".to_string(),
+            Box::new(client_reader),
+            Box::new(client_writer),
+            Duration::from_millis(50),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "MCP server silent did not answer initialize"
+        );
+    }
+
+    #[tokio::test]
+    async fn mcp_client_lists_and_calls_tools() {
+        let client = fake_mcp_client("fake").await;
+
+        let tools = client.list_tools().await.unwrap();
+        assert_eq!(tools.len(), 2);
+        assert_eq!(tools[0].name, "echo");
+        assert_eq!(
+            tools[0].description,
+            Some("Echo the given text".to_string())
+        );
+        assert_eq!(tools[1].description, None);
+
+        let echoed = client
+            .call_tool("echo", serde_json::json!({"text": "moi"}))
+            .await
+            .unwrap();
+        assert_eq!(echoed, Ok("moi".to_string()));
+
+        let failed = client
+            .call_tool("fail", serde_json::json!({}))
+            .await
+            .unwrap();
+        assert_eq!(failed, Err("tool failed".to_string()));
+    }
+
+    #[test]
+    fn mcp_server_config_parse() {
+        assert_eq!(
+            McpServerConfig::parse("issues=/usr/local/bin/issues-mcp --stdio --verbose"),
+            Some(McpServerConfig {
+                name: "issues".to_string(),
+                command: "/usr/local/bin/issues-mcp".to_string(),
+                args: vec!["--stdio".to_string(), "--verbose".to_string()],
+            })
+        );
+        assert_eq!(McpServerConfig::parse("issues="), None);
+        assert_eq!(McpServerConfig::parse("no-command"), None);
+    }
+
+    #[test]
+    fn mcp_allow_list_rules() {
+        let allow_list = McpAllowList::new(&["issues/search".to_string(), "docs/*".to_string()]);
+
+        assert!(allow_list.allows("issues", "search"));
+        assert!(!allow_list.allows("issues", "close"));
+        assert!(allow_list.allows("docs", "anything"));
+        assert!(!allow_list.allows("other", "search"));
+    }
+}
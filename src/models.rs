@@ -1,9 +1,219 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(Debug, Deserialize)]
 pub struct Delta {
     pub role: Option<String>,
     pub content: Option<String>,
+    pub tool_calls: Option<Vec<ToolCallDelta>>,
+}
+
+/// Fragment of a tool call as streamed in a chunk delta, the arguments can be split over
+/// several chunks.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ToolCallDelta {
+    pub index: Option<usize>,
+    pub id: Option<String>,
+    pub function: Option<FunctionCallDelta>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct FunctionCallDelta {
+    pub name: Option<String>,
+    pub arguments: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct FunctionCall {
+    pub name: String,
+    pub arguments: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ToolCall {
+    pub id: String,
+    pub r#type: String,
+    pub function: FunctionCall,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContentPart {
+    Text { text: String },
+    ImageUrl { image_url: String },
+}
+
+/// Message content, plain text or multimodal parts such as images as base64 data URLs.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum MessageContent {
+    Text(String),
+    Parts(Vec<ContentPart>),
+}
+
+impl MessageContent {
+    /// The text of the message, images are left out.
+    pub fn text(&self) -> String {
+        match self {
+            MessageContent::Text(text) => text.clone(),
+            MessageContent::Parts(parts) => parts
+                .iter()
+                .filter_map(|part| match part {
+                    ContentPart::Text { text } => Some(text.as_str()),
+                    ContentPart::ImageUrl { .. } => None,
+                })
+                .collect::<Vec<&str>>()
+                .join("\n"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: MessageContent,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl ChatMessage {
+    pub fn new(role: &str, content: String) -> Self {
+        ChatMessage {
+            role: role.to_string(),
+            content: MessageContent::Text(content),
+            tool_calls: None,
+            tool_call_id: None,
+            name: None,
+        }
+    }
+
+    pub fn user(content: String) -> Self {
+        Self::new("user", content)
+    }
+
+    /// User message with the text followed by one part per image data URL.
+    pub fn user_with_images(content: String, image_urls: Vec<String>) -> Self {
+        if image_urls.is_empty() {
+            return Self::user(content);
+        }
+
+        let mut parts = vec![ContentPart::Text { text: content }];
+        parts.extend(
+            image_urls
+                .into_iter()
+                .map(|image_url| ContentPart::ImageUrl { image_url }),
+        );
+
+        ChatMessage {
+            content: MessageContent::Parts(parts),
+            ..Self::new("user", String::new())
+        }
+    }
+
+    pub fn assistant(content: String, tool_calls: Vec<ToolCall>) -> Self {
+        ChatMessage {
+            tool_calls: (!tool_calls.is_empty()).then_some(tool_calls),
+            ..Self::new("assistant", content)
+        }
+    }
+
+    pub fn tool(call: &ToolCall, content: String) -> Self {
+        ChatMessage {
+            tool_call_id: Some(call.id.clone()),
+            name: Some(call.function.name.clone()),
+            ..Self::new("tool", content)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FunctionDefinition {
+    pub name: String,
+    pub description: String,
+    pub parameters: serde_json::Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ToolDefinition {
+    pub r#type: String,
+    pub function: FunctionDefinition,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JsonSchemaFormat {
+    pub name: String,
+    pub schema: serde_json::Value,
+    pub strict: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    Text,
+    JsonObject,
+    JsonSchema { json_schema: JsonSchemaFormat },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatCompletionRequest {
+    pub model: String,
+    pub messages: Vec<ChatMessage>,
+    pub stream: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<ToolDefinition>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+}
+
+impl ChatCompletionRequest {
+    pub fn new(model: String, messages: Vec<ChatMessage>) -> Self {
+        ChatCompletionRequest {
+            model,
+            messages,
+            stream: true,
+            tools: Vec::new(),
+            tool_choice: None,
+            response_format: None,
+            temperature: None,
+            top_p: None,
+            max_tokens: None,
+        }
+    }
+}
+
+/// Assistant message of a non-streamed completion, `content` is empty for tool calls.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatCompletionResponseMessage {
+    pub role: String,
+    pub content: Option<String>,
+    pub tool_calls: Option<Vec<ToolCall>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatCompletionResponseChoice {
+    pub index: u32,
+    pub message: ChatCompletionResponseMessage,
+    pub finish_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChatCompletionResponse {
+    pub id: String,
+    pub model: String,
+    pub choices: Vec<ChatCompletionResponseChoice>,
+    pub usage: Option<UsageInfo>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -13,24 +223,32 @@ pub struct ChatCompletionChoice {
     pub delta: Delta,
 }
 
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct UsageInfo {
+    pub prompt_tokens: u64,
+    #[serde(default)]
+    pub completion_tokens: u64,
+    pub total_tokens: u64,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ChatCompletionChunk {
     pub id: String,
     pub created: u64,
     pub model: String,
     pub object: Option<String>,
-    pub usage: Option<String>,
+    pub usage: Option<UsageInfo>,
     pub p: Option<String>,
     pub choices: Vec<ChatCompletionChoice>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct MistralModelResponse {
     pub data: Vec<MistralModelCard>,
     pub object: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct MistralModelCard {
     pub aliases: Vec<String>,
     pub archived: Option<bool>,
@@ -51,7 +269,7 @@ pub struct MistralModelCard {
     pub max_content_length: Option<i64>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct MistralModelCapabilities {
     pub audio: bool,
     pub classification: bool,
@@ -64,6 +282,380 @@ pub struct MistralModelCapabilities {
     pub vision: bool,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ModelCapability {
+    Audio,
+    Classification,
+    CompletionChat,
+    CompletionFim,
+    FineTuning,
+    FunctionCalling,
+    Moderation,
+    Ocr,
+    Vision,
+}
+
+impl MistralModelCapabilities {
+    pub fn supports(&self, capability: ModelCapability) -> bool {
+        match capability {
+            ModelCapability::Audio => self.audio,
+            ModelCapability::Classification => self.classification,
+            ModelCapability::CompletionChat => self.completion_chat,
+            ModelCapability::CompletionFim => self.completion_fim,
+            ModelCapability::FineTuning => self.fine_tuning,
+            ModelCapability::FunctionCalling => self.function_calling,
+            ModelCapability::Moderation => self.moderation,
+            ModelCapability::Ocr => self.ocr,
+            ModelCapability::Vision => self.vision,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FimCompletionRequest {
+    pub model: String,
+    pub prompt: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suffix: Option<String>,
+    pub stream: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EmbeddingRequest {
+    pub model: String,
+    pub input: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EmbeddingData {
+    pub embedding: Vec<f32>,
+    pub index: usize,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct EmbeddingResponse {
+    pub id: String,
+    pub model: String,
+    pub data: Vec<EmbeddingData>,
+    pub usage: UsageInfo,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OcrDocument {
+    DocumentUrl { document_url: String },
+    ImageUrl { image_url: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OcrRequest {
+    pub model: String,
+    pub document: OcrDocument,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct OcrPageDimensions {
+    pub dpi: u32,
+    pub height: u32,
+    pub width: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct OcrPage {
+    pub index: u32,
+    pub markdown: String,
+    pub dimensions: Option<OcrPageDimensions>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct OcrUsageInfo {
+    pub pages_processed: u32,
+    pub doc_size_bytes: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct OcrResponse {
+    pub model: String,
+    pub pages: Vec<OcrPage>,
+    pub usage_info: Option<OcrUsageInfo>,
+}
+
+/// Audio upload for the transcription endpoint, sent as a multipart form.
+#[derive(Debug, Clone)]
+pub struct TranscriptionRequest {
+    pub model: String,
+    pub file_name: String,
+    pub mime_type: String,
+    pub audio: Vec<u8>,
+    pub language: Option<String>,
+    pub segment_timestamps: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TranscriptionSegment {
+    pub text: String,
+    pub start: f64,
+    pub end: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TranscriptionResponse {
+    pub model: String,
+    pub text: String,
+    pub language: Option<String>,
+    #[serde(default)]
+    pub segments: Vec<TranscriptionSegment>,
+}
+
+/// Input for the moderation and classification endpoints.
+#[derive(Debug, Clone, Serialize)]
+pub struct ModerationRequest {
+    pub model: String,
+    pub input: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ModerationResult {
+    pub categories: BTreeMap<String, bool>,
+    pub category_scores: BTreeMap<String, f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ModerationResponse {
+    pub id: String,
+    pub model: String,
+    pub results: Vec<ModerationResult>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ClassificationTarget {
+    pub scores: BTreeMap<String, f64>,
+}
+
+/// Scores per input, keyed by the targets the classifier was trained on.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ClassificationResponse {
+    pub id: String,
+    pub model: String,
+    pub results: Vec<BTreeMap<String, ClassificationTarget>>,
+}
+
+/// A file stored on the platform, e.g. batch input or fine-tuning data.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct MistralFile {
+    pub id: String,
+    pub bytes: u64,
+    pub created_at: i64,
+    pub filename: String,
+    pub purpose: String,
+    pub num_lines: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchJobRequest {
+    pub input_files: Vec<String>,
+    pub endpoint: String,
+    pub model: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum BatchJobStatus {
+    Queued,
+    Running,
+    Success,
+    Failed,
+    TimeoutExceeded,
+    CancellationRequested,
+    Cancelled,
+    #[serde(other)]
+    Unknown,
+}
+
+impl BatchJobStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self,
+            BatchJobStatus::Success
+                | BatchJobStatus::Failed
+                | BatchJobStatus::TimeoutExceeded
+                | BatchJobStatus::Cancelled
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct BatchJobError {
+    pub message: String,
+    pub count: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct BatchJob {
+    pub id: String,
+    pub input_files: Vec<String>,
+    pub endpoint: String,
+    pub model: Option<String>,
+    pub output_file: Option<String>,
+    pub error_file: Option<String>,
+    #[serde(default)]
+    pub errors: Vec<BatchJobError>,
+    pub status: BatchJobStatus,
+    pub created_at: i64,
+    pub total_requests: u64,
+    pub completed_requests: u64,
+    pub succeeded_requests: u64,
+    pub failed_requests: u64,
+    pub started_at: Option<i64>,
+    pub completed_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrainingFile {
+    pub file_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weight: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct FineTuningHyperparameters {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub training_steps: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub learning_rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub epochs: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FineTuningJobRequest {
+    pub model: String,
+    pub training_files: Vec<TrainingFile>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub validation_files: Vec<String>,
+    pub hyperparameters: FineTuningHyperparameters,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suffix: Option<String>,
+    pub auto_start: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum FineTuningJobStatus {
+    Queued,
+    Started,
+    Validating,
+    Validated,
+    Running,
+    FailedValidation,
+    Failed,
+    Success,
+    Cancelled,
+    CancellationRequested,
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct FineTuningJob {
+    pub id: String,
+    pub model: String,
+    pub status: FineTuningJobStatus,
+    pub created_at: i64,
+    pub modified_at: Option<i64>,
+    #[serde(default)]
+    pub training_files: Vec<String>,
+    #[serde(default)]
+    pub validation_files: Vec<String>,
+    pub fine_tuned_model: Option<String>,
+    pub suffix: Option<String>,
+    pub hyperparameters: Option<FineTuningHyperparameters>,
+    pub trained_tokens: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FineTuningJobList {
+    pub data: Vec<FineTuningJob>,
+    pub total: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct AgentTool {
+    pub r#type: String,
+}
+
+/// An agent configured in the Mistral console or through the agents API.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Agent {
+    pub id: String,
+    pub name: String,
+    pub model: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tools: Vec<AgentTool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConversationInput {
+    pub role: String,
+    pub content: MessageContent,
+}
+
+/// Starts a conversation with `agent_id`, or appends to an existing one without it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConversationRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent_id: Option<String>,
+    pub inputs: Vec<ConversationInput>,
+    pub stream: bool,
+}
+
+/// Output content is text or a chunk such as a tool reference.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum ConversationContent {
+    Text(String),
+    Chunk(serde_json::Value),
+}
+
+impl ConversationContent {
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            ConversationContent::Text(text) => Some(text),
+            ConversationContent::Chunk(chunk) => chunk
+                .get("text")
+                .and_then(|text| text.as_str())
+                .filter(|_| chunk.get("type").and_then(|kind| kind.as_str()) == Some("text")),
+        }
+    }
+}
+
+/// Server-sent events of a streamed conversation, events not shown are `Other`.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type")]
+pub enum ConversationEvent {
+    #[serde(rename = "conversation.response.started")]
+    Started { conversation_id: String },
+    #[serde(rename = "message.output.delta")]
+    MessageOutputDelta { content: ConversationContent },
+    #[serde(rename = "tool.execution.started")]
+    ToolExecutionStarted { name: String },
+    #[serde(rename = "conversation.response.done")]
+    Done { usage: Option<UsageInfo> },
+    #[serde(rename = "conversation.response.error")]
+    Error { message: String },
+    #[serde(other)]
+    Other,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -153,4 +745,55 @@ mod tests {
         assert_eq!(choice.delta.role, None);
         assert_eq!(choice.delta.content, Some("ku".to_string()));
     }
+
+    #[tokio::test]
+    async fn test_deserialize_chat_completion_chunk_with_tool_calls() {
+        let json_bytes: &[u8] = br#"
+            {
+                "id": "2f0f5f2a6f6e4c9a9f3d1d3a0f3c9b1e",
+                "object": "chat.completion.chunk",
+                "created": 1765372763,
+                "model": "mistral-small-latest",
+                "choices": [
+                    {
+                        "index": 0,
+                        "delta": {
+                            "content": "",
+                            "tool_calls": [
+                                {
+                                    "id": "D681PevKs",
+                                    "function": {
+                                        "name": "read_file",
+                                        "arguments": "{\"path\": \"Cargo.toml\"}"
+                                    },
+                                    "index": 0
+                                }
+                            ]
+                        },
+                        "finish_reason": "tool_calls"
+                    }
+                ],
+                "usage": {"prompt_tokens": 90, "total_tokens": 112, "completion_tokens": 22}
+            }
+        "#;
+
+        let mut buffer = Vec::new();
+        buffer.extend_from_slice(json_bytes);
+
+        let chunk = simd_json::from_slice::<ChatCompletionChunk>(&mut buffer).unwrap();
+        let choice = &chunk.choices[0];
+        let tool_calls = choice.delta.tool_calls.as_ref().unwrap();
+
+        assert_eq!(choice.finish_reason, Some("tool_calls".to_string()));
+        assert_eq!(tool_calls.len(), 1);
+        assert_eq!(tool_calls[0].index, Some(0));
+        assert_eq!(tool_calls[0].id, Some("D681PevKs".to_string()));
+        let function = tool_calls[0].function.as_ref().unwrap();
+        assert_eq!(function.name, Some("read_file".to_string()));
+        assert_eq!(
+            function.arguments,
+            Some("{\"path\": \"Cargo.toml\"}".to_string())
+        );
+        assert_eq!(chunk.usage.unwrap().completion_tokens, 22);
+    }
 }
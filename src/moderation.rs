@@ -0,0 +1,245 @@
+use crate::{
+    client, config,
+    logger::Logger,
+    models::{ModerationRequest, ModerationResult},
+};
+use std::collections::BTreeMap;
+
+pub const DEFAULT_MODERATION_MODEL: &str = "mistral-moderation-latest";
+
+const GUARD_CONFIG_KEY: &str = "MODERATION_GUARD";
+const THRESHOLD_CONFIG_KEY: &str = "MODERATION_THRESHOLD";
+const DEFAULT_THRESHOLD: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GuardMode {
+    Off,
+    Warn,
+    Block,
+}
+
+/// Pre-send moderation check configured with `MODERATION_GUARD="warn|block"` and
+/// `MODERATION_THRESHOLD="category=score"` lines, `*` sets the default threshold.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModerationGuard {
+    pub mode: GuardMode,
+    default_threshold: f64,
+    thresholds: BTreeMap<String, f64>,
+}
+
+impl ModerationGuard {
+    pub fn parse(mode: Option<&str>, thresholds: &[String]) -> Result<Self, String> {
+        let mode = match mode.map(|mode| mode.trim().to_lowercase()).as_deref() {
+            None | Some("") | Some("off") => GuardMode::Off,
+            Some("warn") => GuardMode::Warn,
+            Some("block") => GuardMode::Block,
+            Some(other) => {
+                return Err(format!(
+                    "Invalid {} \"{}\", use off, warn or block",
+                    GUARD_CONFIG_KEY, other
+                ));
+            }
+        };
+
+        let mut guard = ModerationGuard {
+            mode,
+            default_threshold: DEFAULT_THRESHOLD,
+            thresholds: BTreeMap::new(),
+        };
+        for rule in thresholds {
+            let (category, threshold) = rule
+                .split_once('=')
+                .and_then(|(category, threshold)| {
+                    Some((category.trim(), threshold.trim().parse::<f64>().ok()?))
+                })
+                .filter(|(_, threshold)| (0.0..=1.0).contains(threshold))
+                .ok_or_else(|| {
+                    format!(
+                        "Invalid {} \"{}\", use category=0.0..1.0",
+                        THRESHOLD_CONFIG_KEY, rule
+                    )
+                })?;
+
+            if category == "*" {
+                guard.default_threshold = threshold;
+            } else {
+                guard.thresholds.insert(category.to_string(), threshold);
+            }
+        }
+
+        Ok(guard)
+    }
+
+    pub async fn from_config() -> Result<Self, String> {
+        let thresholds = config::get_config_values(THRESHOLD_CONFIG_KEY)
+            .await
+            .unwrap_or_default();
+        Self::parse(
+            config::get_config_value(GUARD_CONFIG_KEY).await.as_deref(),
+            &thresholds,
+        )
+    }
+
+    pub fn threshold(&self, category: &str) -> f64 {
+        self.thresholds
+            .get(category)
+            .copied()
+            .unwrap_or(self.default_threshold)
+    }
+
+    /// Categories scoring at or above their threshold, highest score first.
+    pub fn flagged(&self, result: &ModerationResult) -> Vec<(String, f64)> {
+        let mut flagged = result
+            .category_scores
+            .iter()
+            .filter(|(category, score)| **score >= self.threshold(category))
+            .map(|(category, score)| (category.clone(), *score))
+            .collect::<Vec<(String, f64)>>();
+        flagged.sort_by(|a, b| b.1.total_cmp(&a.1));
+        flagged
+    }
+}
+
+/// One line per category, highest score first, flagged categories marked with `!`.
+pub fn format_scores(result: &ModerationResult) -> String {
+    let mut scores = result
+        .category_scores
+        .iter()
+        .collect::<Vec<(&String, &f64)>>();
+    scores.sort_by(|a, b| b.1.total_cmp(a.1));
+
+    let width = scores
+        .iter()
+        .map(|(category, _)| category.len())
+        .max()
+        .unwrap_or(0);
+    scores
+        .into_iter()
+        .map(|(category, score)| {
+            let flag = if result.categories.get(category).copied().unwrap_or(false) {
+                "!"
+            } else {
+                " "
+            };
+            format!("{} {:width$}  {:.4}", flag, category, score, width = width)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Runs `prompt` through moderation when the guard is enabled.
+///
+/// Flagged prompts are logged, `warn` lets them through and `block` returns an error. A
+/// failing moderation call blocks in `block` mode, the guard must not be skipped silently,
+/// and is warned about in `warn` mode.
+pub async fn guard_prompt(prompt: &str, logger: &Logger) -> Result<(), String> {
+    guard_prompt_with(&ModerationGuard::from_config().await?, prompt, logger).await
+}
+
+/// [`guard_prompt`] with a guard loaded once, e.g. for every line of a batch.
+pub async fn guard_prompt_with(
+    guard: &ModerationGuard,
+    prompt: &str,
+    logger: &Logger,
+) -> Result<(), String> {
+    if guard.mode == GuardMode::Off {
+        return Ok(());
+    }
+
+    let request = ModerationRequest {
+        model: DEFAULT_MODERATION_MODEL.to_string(),
+        input: vec![prompt.to_string()],
+    };
+    let response = match client::call_mistral_moderation(&request, logger).await {
+        Ok(response) => response,
+        Err(e) => {
+            let message = format!("Moderation check failed: {}", e);
+            if guard.mode == GuardMode::Block {
+                return Err(message);
+            }
+            logger.log_warn(message.clone()).await;
+            eprintln!("Warning: {}, sending the prompt unchecked", message);
+            return Ok(());
+        }
+    };
+
+    let flagged = response
+        .results
+        .iter()
+        .flat_map(|result| guard.flagged(result))
+        .map(|(category, score)| format!("{} {:.2}", category, score))
+        .collect::<Vec<String>>();
+    if flagged.is_empty() {
+        return Ok(());
+    }
+
+    let message = format!("Prompt flagged by moderation: {}", flagged.join(", "));
+    logger.log_error(message.clone()).await;
+    match guard.mode {
+        GuardMode::Block => Err(message),
+        _ => {
+            eprintln!("Warning: {}", message);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moderation_result() -> ModerationResult {
+        serde_json::from_value(serde_json::json!({
+            "categories": {"violence_and_threats": true, "pii": false, "selfharm": false},
+            "category_scores": {"violence_and_threats": 0.81, "pii": 0.32, "selfharm": 0.001}
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn guard_parses_modes_and_thresholds() {
+        let guard = ModerationGuard::parse(
+            Some("Block"),
+            &["pii=0.3".to_string(), "* = 0.9".to_string()],
+        )
+        .unwrap();
+
+        assert_eq!(guard.mode, GuardMode::Block);
+        assert_eq!(guard.threshold("pii"), 0.3);
+        assert_eq!(guard.threshold("violence_and_threats"), 0.9);
+        assert_eq!(
+            ModerationGuard::parse(None, &[]).unwrap().mode,
+            GuardMode::Off
+        );
+        assert!(ModerationGuard::parse(Some("deny"), &[]).is_err());
+        assert!(ModerationGuard::parse(Some("warn"), &["pii=1.5".to_string()]).is_err());
+        assert!(ModerationGuard::parse(Some("warn"), &["pii".to_string()]).is_err());
+    }
+
+    #[test]
+    fn guard_flags_categories_over_their_threshold() {
+        let result = moderation_result();
+
+        let default_guard = ModerationGuard::parse(Some("warn"), &[]).unwrap();
+        assert_eq!(
+            default_guard.flagged(&result),
+            vec![("violence_and_threats".to_string(), 0.81)]
+        );
+
+        let strict_guard =
+            ModerationGuard::parse(Some("block"), &["pii=0.3".to_string(), "*=0.9".to_string()])
+                .unwrap();
+        assert_eq!(
+            strict_guard.flagged(&result),
+            vec![("pii".to_string(), 0.32)]
+        );
+    }
+
+    #[test]
+    fn scores_are_sorted_and_flagged() {
+        assert_eq!(
+            format_scores(&moderation_result()),
+            "! violence_and_threats  0.8100\n  pii                   0.3200\n  selfharm              0.0010"
+        );
+    }
+}
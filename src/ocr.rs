@@ -0,0 +1,152 @@
+use crate::{
+    images,
+    models::{OcrDocument, OcrResponse},
+};
+use std::path::Path;
+
+pub const DEFAULT_OCR_MODEL: &str = "mistral-ocr-latest";
+
+const PDF_MIME_TYPE: &str = "application/pdf";
+const MAX_DOCUMENT_BYTES: u64 = 50 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum OcrFormat {
+    /// The pages as one markdown document
+    Markdown,
+    /// The pages with their index, markdown and dimensions
+    Json,
+}
+
+fn is_url(input: &str) -> bool {
+    input.starts_with("https://") || input.starts_with("http://")
+}
+
+/// Builds the document for a local PDF or image, URLs are passed on as they are.
+pub async fn ocr_document(input: &str) -> Result<OcrDocument, Box<dyn std::error::Error>> {
+    let path = Path::new(input);
+    let is_image = images::image_mime_type(path).is_some();
+
+    if is_url(input) {
+        return Ok(if is_image {
+            OcrDocument::ImageUrl {
+                image_url: input.to_string(),
+            }
+        } else {
+            OcrDocument::DocumentUrl {
+                document_url: input.to_string(),
+            }
+        });
+    }
+
+    if is_image {
+        return Ok(OcrDocument::ImageUrl {
+            image_url: images::image_data_url(path).await?,
+        });
+    }
+
+    let is_pdf = path
+        .extension()
+        .map(|extension| extension.eq_ignore_ascii_case("pdf"))
+        .unwrap_or(false);
+    if !is_pdf {
+        return Err(format!(
+            "Unsupported document {}, use a pdf, png, jpeg, gif or webp",
+            input
+        )
+        .into());
+    }
+
+    Ok(OcrDocument::DocumentUrl {
+        document_url: images::file_data_url(path, PDF_MIME_TYPE, MAX_DOCUMENT_BYTES).await?,
+    })
+}
+
+/// Joins the pages into one markdown document, separated by horizontal rules.
+pub fn to_markdown(response: &OcrResponse) -> String {
+    response
+        .pages
+        .iter()
+        .map(|page| page.markdown.trim())
+        .collect::<Vec<&str>>()
+        .join("\n\n---\n\n")
+}
+
+pub fn format_response(
+    response: &OcrResponse,
+    format: OcrFormat,
+) -> Result<String, serde_json::Error> {
+    match format {
+        OcrFormat::Markdown => Ok(to_markdown(response)),
+        OcrFormat::Json => serde_json::to_string_pretty(response),
+    }
+}
+
+/// Prompt for a follow-up run with the recognized text attached.
+pub fn follow_up_prompt(prompt: &str, source: &str, response: &OcrResponse) -> String {
+    format!(
+        "{}\n\n<document source=\"{}\">\n{}\n</document>",
+        prompt,
+        source,
+        to_markdown(response)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ocr_response() -> OcrResponse {
+        serde_json::from_value(serde_json::json!({
+            "model": "mistral-ocr-2505",
+            "pages": [
+                {
+                    "index": 0,
+                    "markdown": "# Invoice 42\n",
+                    "images": [],
+                    "dimensions": {"dpi": 200, "height": 2200, "width": 1700}
+                },
+                {"index": 1, "markdown": "Total: 12,50 €", "images": [], "dimensions": null}
+            ],
+            "usage_info": {"pages_processed": 2, "doc_size_bytes": 5120}
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn markdown_and_json_output() {
+        let response = ocr_response();
+
+        assert_eq!(
+            format_response(&response, OcrFormat::Markdown).unwrap(),
+            "# Invoice 42\n\n---\n\nTotal: 12,50 €"
+        );
+
+        let json = serde_json::from_str::<serde_json::Value>(
+            &format_response(&response, OcrFormat::Json).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(json["pages"][0]["dimensions"]["dpi"], 200);
+        assert_eq!(json["pages"][1]["markdown"], "Total: 12,50 €");
+        assert_eq!(json["usage_info"]["pages_processed"], 2);
+    }
+
+    #[tokio::test]
+    async fn documents_for_urls_and_unsupported_files() {
+        assert_eq!(
+            ocr_document("https://example.com/scan.pdf").await.unwrap(),
+            OcrDocument::DocumentUrl {
+                document_url: "https://example.com/scan.pdf".to_string()
+            }
+        );
+        assert_eq!(
+            serde_json::to_value(
+                ocr_document("https://example.com/receipt.jpg")
+                    .await
+                    .unwrap()
+            )
+            .unwrap(),
+            serde_json::json!({"type": "image_url", "image_url": "https://example.com/receipt.jpg"})
+        );
+        assert!(ocr_document("notes.docx").await.is_err());
+    }
+}
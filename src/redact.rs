@@ -0,0 +1,166 @@
+use crate::config;
+use regex::Regex;
+use std::{borrow::Cow, env};
+
+const REDACT_CONFIG_KEY: &str = "LOG_REDACT";
+const PATTERN_CONFIG_KEY: &str = "LOG_REDACT_PATTERN";
+const MASK: &str = "[REDACTED]";
+// the key itself is masked wherever it shows up, short values would mask too much
+const MIN_KEY_LEN: usize = 8;
+
+const SECRET_PATTERNS: [(&str, &str); 2] = [
+    (r"(?i)(bearer\s+)[A-Za-z0-9._~+/=-]{8,}", "${1}[REDACTED]"),
+    (
+        r#"(?i)("?(?:api[_-]?key|access[_-]?token|secret|password)"?\s*[:=]\s*"?)[^"\s,}]{6,}"#,
+        "${1}[REDACTED]",
+    ),
+];
+// JSON string fields holding prompts, answers and tool arguments
+const CONTENT_PATTERN: (&str, &str) = (
+    r#"("(?:content|text|prompt|input|arguments)"\s*:\s*)"(?:[^"\\]|\\.)*""#,
+    "${1}\"[REDACTED]\"",
+);
+
+/// Masks secrets, and with `LOG_REDACT="content"` the prompt and response text, before
+/// lines reach `stream.log` and `error.log`. Enabled with `LOG_REDACT="secrets"`, extra
+/// patterns come from repeatable `LOG_REDACT_PATTERN` lines.
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    rules: Vec<(Regex, String)>,
+}
+
+impl Redactor {
+    pub fn new(
+        mask_content: bool,
+        api_key: Option<&str>,
+        patterns: &[String],
+    ) -> Result<Self, String> {
+        let mut rules = SECRET_PATTERNS
+            .iter()
+            .map(|(pattern, replacement)| (Regex::new(pattern).unwrap(), replacement.to_string()))
+            .collect::<Vec<(Regex, String)>>();
+
+        if let Some(api_key) = api_key
+            .map(str::trim)
+            .filter(|key| key.len() >= MIN_KEY_LEN)
+        {
+            rules.push((
+                Regex::new(&regex::escape(api_key)).unwrap(),
+                MASK.to_string(),
+            ));
+        }
+        for pattern in patterns {
+            let regex = Regex::new(pattern)
+                .map_err(|e| format!("Invalid {} \"{}\": {}", PATTERN_CONFIG_KEY, pattern, e))?;
+            rules.push((regex, MASK.to_string()));
+        }
+        if mask_content {
+            rules.push((
+                Regex::new(CONTENT_PATTERN.0).unwrap(),
+                CONTENT_PATTERN.1.to_string(),
+            ));
+        }
+
+        Ok(Redactor { rules })
+    }
+
+    /// The redactor configured in `config.jwek`, `None` when redaction is off.
+    pub async fn from_config() -> Result<Option<Self>, String> {
+        let mask_content = match config::get_config_value(REDACT_CONFIG_KEY)
+            .await
+            .map(|value| value.trim().to_lowercase())
+            .as_deref()
+        {
+            None | Some("") | Some("off") => return Ok(None),
+            Some("secrets") => false,
+            Some("content") => true,
+            Some(other) => {
+                return Err(format!(
+                    "Invalid {} \"{}\", use off, secrets or content",
+                    REDACT_CONFIG_KEY, other
+                ));
+            }
+        };
+
+        let patterns = config::get_config_values(PATTERN_CONFIG_KEY)
+            .await
+            .unwrap_or_default();
+        Self::new(
+            mask_content,
+            env::var("MISTRAL_API_KEY").ok().as_deref(),
+            &patterns,
+        )
+        .map(Some)
+    }
+
+    pub fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut redacted = Cow::Borrowed(text);
+        for (regex, replacement) in &self.rules {
+            if let Cow::Owned(replaced) = regex.replace_all(&redacted, replacement.as_str()) {
+                redacted = Cow::Owned(replaced);
+            }
+        }
+        redacted
+    }
+
+    /// [`Redactor::redact`] for raw bytes, bytes that are not valid UTF-8 are kept as
+    /// they are.
+    pub fn redact_bytes(&self, bytes: &[u8]) -> Vec<u8> {
+        let mut redacted = Vec::with_capacity(bytes.len());
+        for chunk in bytes.utf8_chunks() {
+            redacted.extend_from_slice(self.redact(chunk.valid()).as_bytes());
+            redacted.extend_from_slice(chunk.invalid());
+        }
+        redacted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secrets_are_masked() {
+        let redactor =
+            Redactor::new(false, Some("k3yV4lu3xyz"), &[r"ORDER-\d{6}".to_string()]).unwrap();
+
+        assert_eq!(
+            redactor.redact("Authorization: Bearer abc.DEF-123_456"),
+            "Authorization: Bearer [REDACTED]"
+        );
+        assert_eq!(
+            redactor.redact(r#"{"api_key": "sk-live-123456", "model": "tiny"}"#),
+            r#"{"api_key": "[REDACTED]", "model": "tiny"}"#
+        );
+        assert_eq!(
+            redactor.redact("key k3yV4lu3xyz rejected for ORDER-123456"),
+            "key [REDACTED] rejected for [REDACTED]"
+        );
+        assert!(matches!(redactor.redact("nothing here"), Cow::Borrowed(_)));
+        assert!(Redactor::new(false, None, &["(".to_string()]).is_err());
+    }
+
+    #[test]
+    fn content_is_masked_when_enabled() {
+        let chunk = r#"data: {"choices":[{"delta":{"content":"Moi \"maailma\""}}],"model":"tiny"}"#;
+
+        assert_eq!(
+            Redactor::new(false, None, &[]).unwrap().redact(chunk),
+            chunk
+        );
+        assert_eq!(
+            Redactor::new(true, None, &[]).unwrap().redact(chunk),
+            r#"data: {"choices":[{"delta":{"content":"[REDACTED]"}}],"model":"tiny"}"#
+        );
+    }
+
+    #[test]
+    fn invalid_bytes_survive_redaction() {
+        let redactor = Redactor::new(false, None, &[]).unwrap();
+
+        assert_eq!(
+            redactor.redact_bytes(b"\xc3 Bearer abcdefgh123 \xa4"),
+            b"\xc3 Bearer [REDACTED] \xa4"
+        );
+    }
+}
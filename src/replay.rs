@@ -0,0 +1,322 @@
+use crate::{logger, stream::ChunkTrace};
+use bytes::Bytes;
+use futures::{StreamExt, stream::BoxStream};
+use std::time::Duration;
+
+/// One chunk read back from `stream.log`, lines written before run ids or stream numbers
+/// were added have none.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedChunk {
+    pub epoch: u64,
+    pub run_id: Option<String>,
+    pub stream: Option<usize>,
+    pub bytes: Vec<u8>,
+}
+
+fn is_run_id(value: &str) -> bool {
+    value.len() == 12 && value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn parse_json_line(line: &str) -> Option<RecordedChunk> {
+    let value = serde_json::from_str::<serde_json::Value>(line).ok()?;
+    Some(RecordedChunk {
+        epoch: value.get("ts")?.as_u64()?,
+        run_id: value
+            .get("run_id")
+            .and_then(|run_id| run_id.as_str())
+            .map(str::to_string),
+        stream: value
+            .get("stream")
+            .and_then(|stream| stream.as_u64())
+            .map(|stream| stream as usize),
+        bytes: match value.get("chunk_escaped") {
+            Some(escaped) => logger::unescape_line(escaped.as_str()?),
+            None => value.get("chunk")?.as_str()?.as_bytes().to_vec(),
+        },
+    })
+}
+
+/// Reads the chunks of a stream log in the text or JSON format.
+///
+/// Older logs wrote the raw chunk after `epoch|`, so its line breaks continue on the next
+/// lines. Those lines are joined back onto the chunk they belong to.
+pub fn parse_stream_log(text: &str) -> Vec<RecordedChunk> {
+    let mut chunks: Vec<RecordedChunk> = Vec::new();
+
+    for line in text.strip_suffix('\n').unwrap_or(text).split('\n') {
+        if line.starts_with('{')
+            && let Some(chunk) = parse_json_line(line)
+        {
+            chunks.push(chunk);
+            continue;
+        }
+
+        let record = line
+            .split_once('|')
+            .and_then(|(epoch, rest)| Some((epoch.parse::<u64>().ok()?, rest)));
+        match record {
+            Some((epoch, rest)) => {
+                // `run_id/stream`, the stream number is missing from older lines
+                let field = rest.split_once('|').map(|(field, chunk)| {
+                    let (run_id, stream) = match field.split_once('/') {
+                        Some((run_id, stream)) => (run_id, stream.parse::<usize>().ok()),
+                        None => (field, None),
+                    };
+                    (run_id, stream, chunk)
+                });
+                let chunk = match field {
+                    Some((run_id, stream, chunk)) if is_run_id(run_id) => RecordedChunk {
+                        epoch,
+                        run_id: Some(run_id.to_string()),
+                        stream,
+                        bytes: logger::unescape_line(chunk),
+                    },
+                    _ => RecordedChunk {
+                        epoch,
+                        run_id: None,
+                        stream: None,
+                        bytes: rest.as_bytes().to_vec(),
+                    },
+                };
+                chunks.push(chunk);
+            }
+            None => {
+                if let Some(previous) = chunks.last_mut().filter(|chunk| chunk.run_id.is_none()) {
+                    previous.bytes.push(b'\n');
+                    previous.bytes.extend_from_slice(line.as_bytes());
+                }
+            }
+        }
+    }
+
+    chunks
+}
+
+/// Chunks of `run_id`, or of the last run in the log.
+pub fn select_run(
+    chunks: &[RecordedChunk],
+    run_id: Option<&str>,
+) -> Result<Vec<RecordedChunk>, String> {
+    let run_id = match run_id {
+        Some(run_id) => Some(run_id.to_string()),
+        None => chunks
+            .last()
+            .ok_or_else(|| "The stream log is empty".to_string())?
+            .run_id
+            .clone(),
+    };
+
+    let selected = chunks
+        .iter()
+        .filter(|chunk| chunk.run_id == run_id)
+        .cloned()
+        .collect::<Vec<RecordedChunk>>();
+    if selected.is_empty() {
+        return Err(format!(
+            "No chunks recorded for run {}",
+            run_id.as_deref().unwrap_or("-")
+        ));
+    }
+    Ok(selected)
+}
+
+fn ends_stream(bytes: &[u8]) -> bool {
+    let text = String::from_utf8_lossy(bytes);
+    text.contains("[DONE]")
+        || text.contains("\"finish_reason\":\"")
+        || text.contains("\"finish_reason\": \"")
+}
+
+fn is_done_only(bytes: &[u8]) -> bool {
+    let text = String::from_utf8_lossy(bytes);
+    text.trim().trim_start_matches("data:").trim() == "[DONE]"
+}
+
+/// Splits a run into its completions, e.g. the rounds of a tool conversation or the
+/// models of a compare run, in the order they started. Chunks are grouped by their
+/// stream number. In logs without one, a new completion starts after a finished chunk
+/// and a trailing `[DONE]` stays with its stream.
+pub fn split_streams(chunks: Vec<RecordedChunk>) -> Vec<Vec<RecordedChunk>> {
+    if !chunks.is_empty() && chunks.iter().all(|chunk| chunk.stream.is_some()) {
+        let mut streams: Vec<Vec<RecordedChunk>> = Vec::new();
+        for chunk in chunks {
+            match streams
+                .iter_mut()
+                .find(|stream| stream[0].stream == chunk.stream)
+            {
+                Some(stream) => stream.push(chunk),
+                None => streams.push(vec![chunk]),
+            }
+        }
+        return streams;
+    }
+
+    let mut streams = vec![Vec::new()];
+    let mut finished = false;
+
+    for chunk in chunks {
+        if finished && !is_done_only(&chunk.bytes) {
+            streams.push(Vec::new());
+            finished = false;
+        }
+        finished = finished || ends_stream(&chunk.bytes);
+        streams.last_mut().unwrap().push(chunk);
+    }
+
+    streams
+}
+
+/// Feeds the chunks back as a response body, waiting the recorded time between them
+/// unless `fast` is set.
+pub fn replay_stream(
+    chunks: Vec<RecordedChunk>,
+    fast: bool,
+) -> BoxStream<'static, Result<Bytes, reqwest::Error>> {
+    let mut previous = chunks.first().map(|chunk| chunk.epoch).unwrap_or(0);
+    let timed = chunks
+        .into_iter()
+        .map(|chunk| {
+            let delay = if fast {
+                Duration::ZERO
+            } else {
+                Duration::from_millis(chunk.epoch.saturating_sub(previous))
+            };
+            previous = chunk.epoch;
+            (delay, Bytes::from(chunk.bytes))
+        })
+        .collect::<Vec<(Duration, Bytes)>>();
+
+    futures::stream::iter(timed)
+        .then(|(delay, bytes)| async move {
+            tokio::time::sleep(delay).await;
+            Ok(bytes)
+        })
+        .boxed()
+}
+
+/// One line per recorded chunk with its offset and the parser's decision, chunks after
+/// the parser stopped are marked as not read.
+pub fn format_report(chunks: &[RecordedChunk], traces: &[ChunkTrace]) -> String {
+    let start = chunks.first().map(|chunk| chunk.epoch).unwrap_or(0);
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(position, chunk)| {
+            let offset = format!("+{:>6} ms", chunk.epoch.saturating_sub(start));
+            match traces.iter().find(|trace| trace.index == position + 1) {
+                Some(trace) => format!("{}  {}", offset, trace),
+                None => format!(
+                    "{}  #{} ({} bytes): not read",
+                    offset,
+                    position + 1,
+                    chunk.bytes.len()
+                ),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// A test for the `stream.rs` test module asserting the content parsed from the chunks.
+pub fn regression_test(name: &str, chunks: &[RecordedChunk], expected: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    let specimen = chunks
+        .iter()
+        .map(|chunk| {
+            format!(
+                "            {:?}.to_string(),\n",
+                String::from_utf8_lossy(&chunk.bytes)
+            )
+        })
+        .collect::<String>();
+
+    format!(
+        "    #[tokio::test]
+    async fn stream_test_specimen_{}() {{
+        let specimen_vect_stream_text: Vec<String> = vec![
+{}        ];
+        let result = test_parse_mistral_stream(specimen_vect_stream_text)
+            .await
+            .unwrap();
+        assert_eq!(result, {:?});
+    }}
+",
+        name, specimen, expected
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stream_log_formats_are_read_back() {
+        let log = "1750000000000|data: {\"a\":1}\n\n\n\
+                   1750000000040|data: {\"a\":\n\
+                   1750000000100|0123456789ab/2|data: {\"b\":\"x\\\\ny\"}\\n\\n\n\
+                   {\"ts\":1750000000200,\"run_id\":\"0123456789ab\",\"chunk\":\"data: [DONE]\\n\\n\"}\n\
+                   {\"ts\":1750000000300,\"run_id\":\"0123456789ab\",\"chunk_escaped\":\"\\\\xc3\"}\n";
+        let chunks = parse_stream_log(log);
+
+        assert_eq!(chunks.len(), 5);
+        assert_eq!(chunks[0].bytes, b"data: {\"a\":1}\n\n");
+        assert_eq!(chunks[1].bytes, b"data: {\"a\":");
+        assert_eq!(chunks[2].run_id.as_deref(), Some("0123456789ab"));
+        assert_eq!(chunks[2].stream, Some(2));
+        assert_eq!(chunks[3].stream, None);
+        assert_eq!(chunks[2].bytes, b"data: {\"b\":\"x\\ny\"}\n\n");
+        assert_eq!(chunks[3].bytes, b"data: [DONE]\n\n");
+        assert_eq!(chunks[4].bytes, b"\xc3");
+
+        let last_run = select_run(&chunks, None).unwrap();
+        assert_eq!(last_run.len(), 3);
+        assert_eq!(
+            select_run(&chunks, Some("ffffffffffff")).unwrap_err(),
+            "No chunks recorded for run ffffffffffff"
+        );
+    }
+
+    #[test]
+    fn runs_split_into_completions() {
+        let chunk = |epoch: u64, text: &str| RecordedChunk {
+            epoch,
+            run_id: None,
+            stream: None,
+            bytes: text.as_bytes().to_vec(),
+        };
+        let streams = split_streams(vec![
+            chunk(0, "data: {\"finish_reason\":null}"),
+            chunk(10, "data: {\"finish_reason\":\"tool_calls\"}"),
+            chunk(20, "data: [DONE]"),
+            chunk(900, "data: {\"finish_reason\":null}"),
+            chunk(950, "data: {\"finish_reason\":\"stop\"}\n\ndata: [DONE]"),
+        ]);
+
+        assert_eq!(
+            streams.iter().map(Vec::len).collect::<Vec<usize>>(),
+            vec![3, 2]
+        );
+
+        // streams of a compare run interleave, their numbers keep them apart
+        let numbered = |stream: usize, text: &str| RecordedChunk {
+            stream: Some(stream),
+            ..chunk(0, text)
+        };
+        let compared = split_streams(vec![
+            numbered(1, "data: {\"content\":\"a\"}"),
+            numbered(2, "data: {\"content\":\"b\"}"),
+            numbered(1, "data: {\"finish_reason\":\"stop\"}"),
+            numbered(2, "data: {\"finish_reason\":\"stop\"}"),
+        ]);
+        assert_eq!(compared.len(), 2);
+        assert!(compared[1].iter().all(|chunk| chunk.stream == Some(2)));
+
+        let test = regression_test("replay 0123", &streams[1], "Moi \"maailma\"");
+        assert!(test.contains("async fn stream_test_specimen_replay_0123()"));
+        assert!(test.contains(r#"            "data: {\"finish_reason\":null}".to_string(),"#));
+        assert!(test.contains(r#"assert_eq!(result, "Moi \"maailma\"");"#));
+    }
+}
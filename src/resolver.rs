@@ -0,0 +1,370 @@
+use crate::models::{MistralModelCard, ModelCapability};
+use crate::{client, config, file, logger::Logger};
+use std::env;
+
+/// Environment variable that overrides the configured model for every invocation.
+pub const MODEL_ENV_VAR: &str = "MISTRAL_MODEL";
+
+const MAX_SUGGESTIONS: usize = 5;
+
+#[derive(Debug, PartialEq)]
+pub enum ModelMatchError {
+    NotFound {
+        query: String,
+        suggestions: Vec<String>,
+    },
+    Ambiguous {
+        query: String,
+        candidates: Vec<String>,
+    },
+}
+
+impl std::fmt::Display for ModelMatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ModelMatchError::NotFound { query, suggestions } if suggestions.is_empty() => {
+                write!(f, "No model matches \"{}\"", query)
+            }
+            ModelMatchError::NotFound { query, suggestions } => write!(
+                f,
+                "No model matches \"{}\". Did you mean: {}?",
+                query,
+                suggestions.join(", ")
+            ),
+            ModelMatchError::Ambiguous { query, candidates } => write!(
+                f,
+                "Model \"{}\" is ambiguous, candidates: {}",
+                query,
+                candidates.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ModelMatchError {}
+
+/// Resolves the model for a single invocation.
+///
+/// Precedence is the CLI flag, then the `MISTRAL_MODEL` environment variable, then the
+/// model saved in the config file. Flag and environment values are fuzzy-matched against
+/// the cached chat models, the saved value is used as is.
+pub async fn resolve_model_name(
+    cli_model: Option<&str>,
+    logger: &Logger,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let env_model = env::var(MODEL_ENV_VAR)
+        .ok()
+        .filter(|value| !value.is_empty());
+
+    let query = match cli_model.map(str::to_string).or(env_model) {
+        Some(query) => query,
+        None => return config::get_model_name().await,
+    };
+
+    match_cached_model(&query, ModelCapability::CompletionChat, logger).await
+}
+
+/// Resolves the model for commands needing a specific capability, e.g. FIM or OCR.
+///
+/// The configured chat model doesn't apply here, so without a CLI flag `default_model` is
+/// used. Flag values are fuzzy-matched against the models supporting `capability` only.
+pub async fn resolve_model_with_capability(
+    cli_model: Option<&str>,
+    default_model: &str,
+    capability: ModelCapability,
+    logger: &Logger,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let query = match cli_model {
+        Some(query) => query,
+        None => return Ok(default_model.to_string()),
+    };
+
+    match_cached_model(query, capability, logger).await
+}
+
+fn with_capability(
+    models: Vec<MistralModelCard>,
+    capability: ModelCapability,
+) -> Vec<MistralModelCard> {
+    models
+        .into_iter()
+        .filter(|model| model.capabilities.supports(capability))
+        .collect()
+}
+
+/// Fuzzy-matches `query` against the cached models supporting `capability`. A miss
+/// refreshes the cache once, so models released after it was written are found too.
+async fn match_cached_model(
+    query: &str,
+    capability: ModelCapability,
+    logger: &Logger,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let cached = file::read_models_cache().await.unwrap_or_default();
+    if !cached.is_empty() {
+        match match_model_name(query, &with_capability(cached, capability)) {
+            Err(ModelMatchError::NotFound { .. }) => {}
+            result => return Ok(result?),
+        }
+    }
+
+    let models = client::fetch_mistral_models(logger).await?;
+    Ok(match_model_name(
+        query,
+        &with_capability(models, capability),
+    )?)
+}
+
+fn find_card(models: Vec<MistralModelCard>, model_name: &str) -> Option<MistralModelCard> {
+    models
+        .into_iter()
+        .find(|model| model.id == model_name || model.aliases.iter().any(|a| a == model_name))
+}
+
+/// Looks up the card of a resolved model, refreshing the cache when it doesn't list the
+/// model. `None` when the model list can't be loaded or the model isn't in it.
+pub async fn find_model_card(model_name: &str, logger: &Logger) -> Option<MistralModelCard> {
+    let cached = file::read_models_cache().await.unwrap_or_default();
+    if let Some(card) = find_card(cached, model_name) {
+        return Some(card);
+    }
+
+    find_card(client::fetch_mistral_models(logger).await.ok()?, model_name)
+}
+
+fn match_score(query: &str, name: &str) -> u8 {
+    let name = name.to_lowercase();
+    if name == query {
+        4
+    } else if name.ends_with(&format!("-{}", query)) || name.starts_with(&format!("{}-", query)) {
+        3
+    } else if name.contains(&format!("-{}-", query)) {
+        2
+    } else if name.contains(query) {
+        1
+    } else {
+        0
+    }
+}
+
+fn card_score(query: &str, card: &MistralModelCard) -> u8 {
+    card.aliases
+        .iter()
+        .map(|alias| match_score(query, alias))
+        .chain(std::iter::once(match_score(query, &card.id)))
+        .max()
+        .unwrap_or(0)
+}
+
+// cards listing each other as aliases point to the same underlying model
+fn same_model(a: &MistralModelCard, b: &MistralModelCard) -> bool {
+    a.id == b.id || a.aliases.contains(&b.id) || b.aliases.contains(&a.id)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b_chars: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b_chars.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b_chars.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b_chars.len()]
+}
+
+/// Picks the single model card matching `query`.
+///
+/// Exact ids and aliases always win. Otherwise the candidates with the best match score are
+/// kept and the query is only accepted if they all refer to the same model.
+pub fn match_model_name(
+    query: &str,
+    models: &[MistralModelCard],
+) -> Result<String, ModelMatchError> {
+    let normalized_query = query.trim().to_lowercase();
+
+    let scored = models
+        .iter()
+        .map(|model| (card_score(&normalized_query, model), model))
+        .filter(|(score, _)| *score > 0)
+        .collect::<Vec<(u8, &MistralModelCard)>>();
+
+    let best_score = match scored.iter().map(|(score, _)| *score).max() {
+        Some(score) => score,
+        None => {
+            let mut suggestions = models
+                .iter()
+                .map(|model| {
+                    (
+                        edit_distance(&normalized_query, &model.id),
+                        model.id.clone(),
+                    )
+                })
+                .collect::<Vec<(usize, String)>>();
+            suggestions.sort();
+
+            return Err(ModelMatchError::NotFound {
+                query: query.to_string(),
+                suggestions: suggestions
+                    .into_iter()
+                    .take(MAX_SUGGESTIONS)
+                    .map(|(_, id)| id)
+                    .collect(),
+            });
+        }
+    };
+
+    let best = scored
+        .into_iter()
+        .filter(|(score, _)| *score == best_score)
+        .map(|(_, model)| model)
+        .collect::<Vec<&MistralModelCard>>();
+
+    if let Some(exact) = best.iter().find(|model| model.id == normalized_query) {
+        return Ok(exact.id.clone());
+    }
+
+    if best.iter().all(|model| same_model(best[0], model)) {
+        let shortest = best
+            .iter()
+            .min_by_key(|model| model.id.len())
+            .expect("best candidates are never empty");
+        return Ok(shortest.id.clone());
+    }
+
+    Err(ModelMatchError::Ambiguous {
+        query: query.to_string(),
+        candidates: best
+            .iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|model| model.id.clone())
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::MistralModelCapabilities;
+
+    fn card(id: &str, aliases: &[&str]) -> MistralModelCard {
+        MistralModelCard {
+            aliases: aliases.iter().map(|alias| alias.to_string()).collect(),
+            archived: None,
+            capabilities: MistralModelCapabilities {
+                audio: false,
+                classification: false,
+                completion_chat: true,
+                completion_fim: false,
+                fine_tuning: false,
+                function_calling: false,
+                moderation: false,
+                ocr: false,
+                vision: false,
+            },
+            created: 0,
+            description: None,
+            default_model_temperature: None,
+            deprecation: None,
+            deprecation_replacement_model: None,
+            id: id.to_string(),
+            job: None,
+            max_context_length: 32000,
+            name: None,
+            object: "model".to_string(),
+            owned_by: "mistralai".to_string(),
+            r#type: "base".to_string(),
+            root: None,
+            max_content_length: None,
+        }
+    }
+
+    fn specimen_models() -> Vec<MistralModelCard> {
+        vec![
+            card("mistral-medium-2505", &[]),
+            card(
+                "mistral-medium-2508",
+                &["mistral-medium-latest", "mistral-medium"],
+            ),
+            card(
+                "mistral-medium-latest",
+                &["mistral-medium-2508", "mistral-medium"],
+            ),
+            card(
+                "mistral-medium",
+                &["mistral-medium-2508", "mistral-medium-latest"],
+            ),
+            card("mistral-tiny", &[]),
+            card("mistral-large-2411", &["mistral-large-latest"]),
+            card("pixtral-large-2411", &["pixtral-large-latest"]),
+        ]
+    }
+
+    #[test]
+    fn match_model_name_exact_id() {
+        let models = specimen_models();
+        assert_eq!(
+            match_model_name("mistral-medium-2505", &models),
+            Ok("mistral-medium-2505".to_string())
+        );
+    }
+
+    #[test]
+    fn match_model_name_short_name_resolves_alias_group() {
+        let models = specimen_models();
+        assert_eq!(
+            match_model_name("medium", &models),
+            Ok("mistral-medium".to_string())
+        );
+        assert_eq!(
+            match_model_name("TINY", &models),
+            Ok("mistral-tiny".to_string())
+        );
+    }
+
+    #[test]
+    fn match_model_name_ambiguous() {
+        let models = specimen_models();
+        match match_model_name("large", &models) {
+            Err(ModelMatchError::Ambiguous { candidates, .. }) => {
+                assert!(candidates.contains(&"mistral-large-2411".to_string()));
+                assert!(candidates.contains(&"pixtral-large-2411".to_string()));
+            }
+            other => panic!("Expected ambiguous match, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn chat_queries_skip_models_without_chat() {
+        let mut embed = card("mistral-embed", &[]);
+        embed.capabilities.completion_chat = false;
+        let mut models = specimen_models();
+        models.push(embed);
+
+        assert_eq!(
+            match_model_name("embed", &models),
+            Ok("mistral-embed".to_string())
+        );
+        assert!(matches!(
+            match_model_name(
+                "embed",
+                &with_capability(models, ModelCapability::CompletionChat)
+            ),
+            Err(ModelMatchError::NotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn match_model_name_not_found_suggests_closest() {
+        let models = specimen_models();
+        match match_model_name("mistral-tinyy", &models) {
+            Err(ModelMatchError::NotFound { suggestions, .. }) => {
+                assert_eq!(suggestions[0], "mistral-tiny");
+            }
+            other => panic!("Expected no match, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use ratatui::{
+    style::{Color, Style},
+    text::{Line, Span, Text},
+    widgets::{Paragraph, Wrap},
+};
+use regex::{Regex, RegexBuilder};
+use std::ops::Range;
+
+/// The `/` search of the token stream. Hits are counted over every pane in order, so
+/// `current` indexes the matches of all panes together.
+#[derive(Debug, Default)]
+pub struct Search {
+    pub query: String,
+    pub editing: bool,
+    pub current: usize,
+    /// Set when the view should scroll to the current match on the next frame.
+    pub jump: bool,
+}
+
+impl Search {
+    pub fn start(&mut self) {
+        self.query.clear();
+        self.editing = true;
+        self.current = 0;
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.current = 0;
+        self.jump = true;
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.current = 0;
+        self.jump = true;
+    }
+
+    pub fn clear(&mut self) {
+        *self = Search::default();
+    }
+
+    /// Moves to the next match, or the previous one when `forward` is false, wrapping around.
+    pub fn step(&mut self, forward: bool, match_count: usize) {
+        if match_count == 0 {
+            return;
+        }
+        self.current = if forward {
+            (self.current + 1) % match_count
+        } else {
+            (self.current + match_count - 1) % match_count
+        };
+        self.jump = true;
+    }
+
+    /// Case-insensitive unless the query has an uppercase letter.
+    pub fn regex(&self) -> Option<Regex> {
+        if self.query.is_empty() {
+            return None;
+        }
+        RegexBuilder::new(&regex::escape(&self.query))
+            .case_insensitive(!self.query.chars().any(char::is_uppercase))
+            .build()
+            .ok()
+    }
+
+    /// `/query 3/12` for the controls bar.
+    pub fn status(&self, match_count: usize) -> String {
+        let cursor = if self.editing { "_" } else { "" };
+        if match_count == 0 {
+            format!("/{}{} no matches", self.query, cursor)
+        } else {
+            format!(
+                "/{}{} {}/{}",
+                self.query,
+                cursor,
+                self.current.min(match_count - 1) + 1,
+                match_count
+            )
+        }
+    }
+}
+
+pub fn find_matches(text: &str, regex: Option<&Regex>) -> Vec<Range<usize>> {
+    regex
+        .map(|regex| regex.find_iter(text).map(|hit| hit.range()).collect())
+        .unwrap_or_default()
+}
+
+/// The text split into lines like `Paragraph::new` does, with the matches highlighted
+/// and `current` standing out.
+pub fn highlight<'a>(text: &'a str, matches: &[Range<usize>], current: Option<usize>) -> Text<'a> {
+    let hit = Style::default().fg(Color::Black).bg(Color::Yellow);
+    let current_hit = Style::default().fg(Color::Black).bg(Color::LightRed);
+
+    let lines = text
+        .lines()
+        .map(|line| {
+            let start = line.as_ptr() as usize - text.as_ptr() as usize;
+            let end = start + line.len();
+            let mut spans = Vec::new();
+            let mut position = start;
+            for (index, range) in matches.iter().enumerate() {
+                if range.end <= start || range.start >= end {
+                    continue;
+                }
+                let from = range.start.max(position);
+                let to = range.end.min(end);
+                if from > position {
+                    spans.push(Span::raw(&text[position..from]));
+                }
+                let style = if Some(index) == current {
+                    current_hit
+                } else {
+                    hit
+                };
+                spans.push(Span::styled(&text[from..to], style));
+                position = to;
+            }
+            if position < end {
+                spans.push(Span::raw(&text[position..end]));
+            }
+            Line::from(spans)
+        })
+        .collect::<Vec<Line>>();
+
+    Text::from(lines)
+}
+
+/// Wrapped row of byte `position` when `text` is laid out `width` columns wide in the
+/// token stream.
+pub fn wrapped_row(text: &str, position: usize, width: u16) -> usize {
+    let line_start = text[..position].rfind('\n').map_or(0, |index| index + 1);
+    // an empty paragraph still lays out one line
+    let rows_before = match line_start {
+        0 => 0,
+        _ => Paragraph::new(&text[..line_start])
+            .wrap(Wrap { trim: true })
+            .line_count(width),
+    };
+    let rows_within = Paragraph::new(&text[line_start..position])
+        .wrap(Wrap { trim: true })
+        .line_count(width);
+    rows_before + rows_within.saturating_sub(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_are_smart_case_and_cycle() {
+        let text = "Moi maailma\n\nMOI taas, moi";
+        let mut search = Search::default();
+        search.start();
+        for c in "moi".chars() {
+            search.push(c);
+        }
+
+        let matches = find_matches(text, search.regex().as_ref());
+        assert_eq!(matches, vec![0..3, 13..16, 23..26]);
+        search.step(false, matches.len());
+        assert_eq!(search.status(matches.len()), "/moi_ 3/3");
+        search.step(true, matches.len());
+        assert_eq!(search.current, 0);
+
+        search.push('T');
+        search.editing = false;
+        assert!(find_matches(text, search.regex().as_ref()).is_empty());
+        assert_eq!(search.status(0), "/moiT no matches");
+    }
+
+    #[test]
+    fn highlighted_lines_keep_the_text() {
+        let text = "a hit\nno\nhit hit";
+        let matches = find_matches(text, Regex::new("hit").ok().as_ref());
+        let highlighted = highlight(text, &matches, Some(1));
+
+        assert_eq!(highlighted.lines.len(), 3);
+        assert_eq!(
+            highlighted.lines[2]
+                .spans
+                .iter()
+                .map(|span| (span.content.as_ref(), span.style.bg))
+                .collect::<Vec<(&str, Option<Color>)>>(),
+            vec![
+                ("hit", Some(Color::LightRed)),
+                (" ", None),
+                ("hit", Some(Color::Yellow))
+            ]
+        );
+    }
+
+    #[test]
+    fn rows_count_wrapped_lines() {
+        let text = "one two three four\n\nfive six";
+
+        assert_eq!(wrapped_row(text, 0, 40), 0);
+        assert_eq!(wrapped_row(text, 14, 10), 1);
+        assert_eq!(wrapped_row(text, 25, 10), 3);
+    }
+}
@@ -1,9 +1,12 @@
-use crate::{logger::Logger, models::ChatCompletionChunk};
+use crate::{
+    logger::Logger,
+    models::{ChatCompletionChunk, FunctionCall, ToolCall, ToolCallDelta, UsageInfo},
+};
 use bytes::Bytes;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 use tokio_stream::StreamExt;
 
-const STREAM_EOS: &'static str = "[DONE]";
+const STREAM_EOS: &str = "[DONE]";
 
 // removes data: prefix and trims whitespace
 fn clean_byte_chunk(buffer: &[u8]) -> Vec<u8> {
@@ -21,6 +24,65 @@ fn clean_byte_chunk(buffer: &[u8]) -> Vec<u8> {
     cleaned.as_bytes().to_vec()
 }
 
+/// A tool call waiting for the user to allow or decline it.
+#[derive(Debug)]
+pub struct ToolApproval {
+    pub call: ToolCall,
+    pub responder: oneshot::Sender<bool>,
+}
+
+/// Updates sent from the stream parser to whoever renders the answer.
+#[derive(Debug)]
+pub enum StreamEvent {
+    Token(String),
+    Usage(UsageInfo),
+    ToolCall(ToolCall),
+    ToolResult { name: String, content: String },
+    ApprovalRequest(ToolApproval),
+}
+
+/// Everything collected from a single streamed completion.
+#[derive(Debug, Default)]
+pub struct StreamOutcome {
+    pub content: String,
+    pub tool_calls: Vec<ToolCall>,
+    pub finish_reason: Option<String>,
+    pub usage: Option<UsageInfo>,
+}
+
+// merges streamed tool call fragments, arguments may arrive split over several chunks
+fn accumulate_tool_calls(tool_calls: &mut Vec<ToolCall>, deltas: &[ToolCallDelta]) {
+    for (position, delta) in deltas.iter().enumerate() {
+        let index = delta.index.unwrap_or(position);
+        if index >= tool_calls.len() {
+            tool_calls.resize_with(index + 1, || ToolCall {
+                id: String::new(),
+                r#type: "function".to_string(),
+                function: FunctionCall {
+                    name: String::new(),
+                    arguments: String::new(),
+                },
+            });
+        }
+
+        let tool_call = &mut tool_calls[index];
+        if let Some(id) = &delta.id
+            && tool_call.id.is_empty()
+        {
+            tool_call.id = id.clone();
+        }
+        if let Some(function) = &delta.function {
+            if let Some(name) = &function.name {
+                tool_call.function.name.push_str(name);
+            }
+            if let Some(arguments) = &function.arguments {
+                tool_call.function.arguments.push_str(arguments);
+            }
+        }
+    }
+}
+
+#[allow(clippy::enum_variant_names)]
 enum StreamState {
     FirstChunk,
     PartialChunk,
@@ -38,19 +100,114 @@ impl std::fmt::Display for StreamState {
 }
 
 pub async fn parse_mistral_stream(
-    mut stream: futures::stream::BoxStream<'static, Result<Bytes, reqwest::Error>>,
-    sender: mpsc::Sender<String>,
+    stream: futures::stream::BoxStream<'static, Result<Bytes, reqwest::Error>>,
+    sender: mpsc::Sender<StreamEvent>,
     logger: &Logger,
 ) -> Result<String, Box<dyn std::error::Error>> {
-    let mut parsed_content = String::new();
+    parse_mistral_stream_outcome(stream, sender, logger)
+        .await
+        .map(|outcome| outcome.content)
+}
+
+/// What the parser did with one chunk, reported by `ai debug replay`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChunkDecision {
+    /// Empty or `[DONE]` chunk
+    Skipped,
+    Parsed {
+        content: Option<String>,
+        finished: bool,
+    },
+    /// Incomplete JSON kept in the buffer for the next chunk
+    Buffered {
+        buffer_len: usize,
+    },
+    BrokenStart,
+    StitchFailed,
+    Error(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkTrace {
+    pub index: usize,
+    pub state: String,
+    pub len: usize,
+    pub decision: ChunkDecision,
+}
+
+impl std::fmt::Display for ChunkTrace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{} {} ({} bytes): ", self.index, self.state, self.len)?;
+        match &self.decision {
+            ChunkDecision::Skipped => write!(f, "skipped"),
+            ChunkDecision::Parsed { content, finished } => {
+                write!(f, "parsed")?;
+                if let Some(content) = content {
+                    write!(f, " {:?}", content)?;
+                }
+                if *finished {
+                    write!(f, ", finished")?;
+                }
+                Ok(())
+            }
+            ChunkDecision::Buffered { buffer_len } => {
+                write!(f, "buffered, {} bytes waiting", buffer_len)
+            }
+            ChunkDecision::BrokenStart => write!(f, "stream starts broken, dropped"),
+            ChunkDecision::StitchFailed => write!(f, "buffer already complete, can't stitch"),
+            ChunkDecision::Error(e) => write!(f, "dropped: {}", e),
+        }
+    }
+}
+
+// logs the decision at trace level and hands it to the replay report when one listens
+async fn trace_chunk(
+    tracer: Option<&mpsc::UnboundedSender<ChunkTrace>>,
+    logger: &Logger,
+    trace: ChunkTrace,
+) {
+    logger.log_trace(format!("Stream chunk {}", trace)).await;
+    if let Some(tracer) = tracer {
+        _ = tracer.send(trace);
+    }
+}
+
+/// Parses the completion stream, forwarding tokens to `sender` and collecting the full
+/// content, tool calls and usage.
+pub async fn parse_mistral_stream_outcome(
+    stream: futures::stream::BoxStream<'static, Result<Bytes, reqwest::Error>>,
+    sender: mpsc::Sender<StreamEvent>,
+    logger: &Logger,
+) -> Result<StreamOutcome, Box<dyn std::error::Error>> {
+    parse_mistral_stream_traced(stream, sender, logger, None).await
+}
+
+/// [`parse_mistral_stream_outcome`] reporting the decision taken for every chunk.
+pub async fn parse_mistral_stream_traced(
+    mut stream: futures::stream::BoxStream<'static, Result<Bytes, reqwest::Error>>,
+    sender: mpsc::Sender<StreamEvent>,
+    logger: &Logger,
+    tracer: Option<mpsc::UnboundedSender<ChunkTrace>>,
+) -> Result<StreamOutcome, Box<dyn std::error::Error>> {
+    let logger = &logger.for_stream();
+    let mut outcome = StreamOutcome::default();
     let mut buffer = Vec::new();
     let mut stream_state = StreamState::FirstChunk;
+    let mut index = 0;
 
     while let Some(chunk) = stream.next().await {
+        index += 1;
         if let Ok(bytes) = chunk {
             logger.write_stream_log(bytes.clone()).await;
+            let mut trace = ChunkTrace {
+                index,
+                state: stream_state.to_string(),
+                len: bytes.len(),
+                decision: ChunkDecision::Skipped,
+            };
             let cleaned_bytes = clean_byte_chunk(&bytes);
             if cleaned_bytes.is_empty() {
+                trace_chunk(tracer.as_ref(), logger, trace).await;
                 continue;
             }
 
@@ -61,16 +218,33 @@ pub async fn parse_mistral_stream(
             match json {
                 Ok(value) => {
                     if let Some(content) = &value.choices[0].delta.content {
-                        parsed_content.push_str(content);
+                        outcome.content.push_str(content);
                         sender
-                            .send(content.clone())
+                            .send(StreamEvent::Token(content.clone()))
                             .await
                             .expect("Failed to send chunk");
                     }
+                    if let Some(tool_calls) = &value.choices[0].delta.tool_calls {
+                        accumulate_tool_calls(&mut outcome.tool_calls, tool_calls);
+                    }
+                    if let Some(usage) = value.usage {
+                        outcome.usage = Some(usage.clone());
+                        sender
+                            .send(StreamEvent::Usage(usage))
+                            .await
+                            .expect("Failed to send usage");
+                    }
                     buffer.clear();
 
+                    trace.decision = ChunkDecision::Parsed {
+                        content: value.choices[0].delta.content.clone(),
+                        finished: value.choices[0].finish_reason.is_some(),
+                    };
+                    trace_chunk(tracer.as_ref(), logger, trace).await;
+
                     // stop if finished
                     if value.choices[0].finish_reason.is_some() {
+                        outcome.finish_reason = value.choices[0].finish_reason.clone();
                         break;
                     }
                 }
@@ -79,11 +253,15 @@ pub async fn parse_mistral_stream(
                     let incoming_chunk_text = String::from_utf8_lossy(&cleaned_bytes);
                     match stream_state {
                         StreamState::FirstChunk if !incoming_chunk_text.starts_with("{") => {
+                            trace.decision = ChunkDecision::BrokenStart;
+                            trace_chunk(tracer.as_ref(), logger, trace).await;
                             logger
-                                .log_error(format!("Token stream starts broken, can't stitch"))
+                                .log_error("Token stream starts broken, can't stitch".to_string())
                                 .await;
                         }
                         StreamState::PartialChunk if current_buffer_text.contains("}") => {
+                            trace.decision = ChunkDecision::StitchFailed;
+                            trace_chunk(tracer.as_ref(), logger, trace).await;
                             logger
                                 .log_error(format!(
                                     "Can't stitch, buffer already contains a complete chunk\n{}",
@@ -100,9 +278,15 @@ pub async fn parse_mistral_stream(
                             stitched_buffer.extend_from_slice(&cleaned_bytes);
                             buffer.extend_from_slice(&stitched_buffer);
                             stream_state = StreamState::PartialChunk;
+                            trace.decision = ChunkDecision::Buffered {
+                                buffer_len: buffer.len(),
+                            };
+                            trace_chunk(tracer.as_ref(), logger, trace).await;
                             continue;
                         }
                         _ => {
+                            trace.decision = ChunkDecision::Error(e.to_string());
+                            trace_chunk(tracer.as_ref(), logger, trace).await;
                             logger
                                 .log_error(format!(
                                     "State: {}\nBroken buffer: {}\nError: {}",
@@ -118,16 +302,18 @@ pub async fn parse_mistral_stream(
         }
         stream_state = StreamState::SubsequentChunk;
     }
+    logger.flush_stream_log().await;
 
     drop(sender);
 
-    Ok(parsed_content)
+    Ok(outcome)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[allow(clippy::let_underscore_future, clippy::redundant_pattern_matching)]
     async fn test_parse_mistral_stream(
         specimen: Vec<String>,
     ) -> Result<String, Box<dyn std::error::Error>> {
@@ -316,4 +502,44 @@ mod tests {
             "The most popular KFC meal can vary depending on the region, but in the United States, the \"Original Recipe Bucket Meal\" is generally the best-selling item. This meal typically includes a bucket of Original Recipe fried chicken, a variety of side options such as mashed potatoes, coleslaw, or biscuits, and a choice of gravy or cornbread. However, it\'s always a good idea to check with your local KFC, as menu offerings and popularity may differ."
         );
     }
+
+    #[tokio::test]
+    async fn stream_test_specimen_tool_call_fragments() {
+        let specimen_vect_stream_text: Vec<String> = vec![
+            "data: {\"id\":\"9c1f0c3a55b24c0f8d4a8f38f5b6a0e1\",\"object\":\"chat.completion.chunk\",\"created\":1765490000,\"model\":\"mistral-small-latest\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"\"},\"finish_reason\":null}]}".to_string(),
+            "data: {\"id\":\"9c1f0c3a55b24c0f8d4a8f38f5b6a0e1\",\"object\":\"chat.completion.chunk\",\"created\":1765490000,\"model\":\"mistral-small-latest\",\"choices\":[{\"index\":0,\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"kX2cVqL9a\",\"function\":{\"name\":\"read_file\",\"arguments\":\"{\\\"pa\"}}]},\"finish_reason\":null}]}".to_string(),
+            "data: {\"id\":\"9c1f0c3a55b24c0f8d4a8f38f5b6a0e1\",\"object\":\"chat.completion.chunk\",\"created\":1765490000,\"model\":\"mistral-small-latest\",\"choices\":[{\"index\":0,\"delta\":{\"tool_calls\":[{\"index\":0,\"function\":{\"arguments\":\"th\\\": \\\"Cargo.toml\\\"}\"}}]},\"finish_reason\":null}]}".to_string(),
+            "data: {\"id\":\"9c1f0c3a55b24c0f8d4a8f38f5b6a0e1\",\"object\":\"chat.completion.chunk\",\"created\":1765490000,\"model\":\"mistral-small-latest\",\"choices\":[{\"index\":0,\"delta\":{\"tool_calls\":[{\"index\":1,\"id\":\"Zp0eR4n2b\",\"function\":{\"name\":\"list_directory\",\"arguments\":\"{\\\"path\\\": \\\"src\\\"}\"}}]},\"finish_reason\":\"tool_calls\"}],\"usage\":{\"prompt_tokens\":120,\"total_tokens\":150,\"completion_tokens\":30}}".to_string(),
+            "data: [DONE]".to_string(),
+        ];
+
+        let stream = futures::stream::iter(
+            specimen_vect_stream_text
+                .into_iter()
+                .map(|s| Ok(Bytes::from(s))),
+        );
+        let (sender, mut receiver) = mpsc::channel(100);
+        let logger = Logger::new().await;
+        tokio::spawn(async move { while receiver.recv().await.is_some() {} });
+
+        let outcome = parse_mistral_stream_outcome(Box::pin(stream), sender, &logger)
+            .await
+            .unwrap();
+
+        assert_eq!(outcome.content, "");
+        assert_eq!(outcome.finish_reason, Some("tool_calls".to_string()));
+        assert_eq!(outcome.usage.unwrap().completion_tokens, 30);
+        assert_eq!(outcome.tool_calls.len(), 2);
+        assert_eq!(outcome.tool_calls[0].id, "kX2cVqL9a");
+        assert_eq!(outcome.tool_calls[0].function.name, "read_file");
+        assert_eq!(
+            outcome.tool_calls[0].function.arguments,
+            "{\"path\": \"Cargo.toml\"}"
+        );
+        assert_eq!(outcome.tool_calls[1].function.name, "list_directory");
+        assert_eq!(
+            outcome.tool_calls[1].function.arguments,
+            "{\"path\": \"src\"}"
+        );
+    }
 }
@@ -0,0 +1,217 @@
+use crate::{
+    logger::Logger,
+    models::{ChatCompletionRequest, ChatMessage, JsonSchemaFormat, ResponseFormat},
+    stream::StreamEvent,
+    tools::{self, ToolRegistry},
+};
+use std::path::Path;
+use tokio::sync::mpsc;
+
+const MAX_REPAIR_ATTEMPTS: usize = 2;
+
+/// How the final answer of a run has to be shaped.
+#[derive(Debug, Clone)]
+pub struct JsonOutput {
+    pub schema: Option<serde_json::Value>,
+    pub repair: bool,
+}
+
+impl JsonOutput {
+    /// `response_format` for the request, `json_schema` when a schema is given.
+    pub fn response_format(&self, schema_name: &str) -> ResponseFormat {
+        match &self.schema {
+            Some(schema) => ResponseFormat::JsonSchema {
+                json_schema: JsonSchemaFormat {
+                    name: schema_name.to_string(),
+                    schema: schema.clone(),
+                    strict: true,
+                },
+            },
+            None => ResponseFormat::JsonObject,
+        }
+    }
+}
+
+pub async fn read_schema_file(
+    path: &Path,
+) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    let contents = tokio::fs::read(path).await?;
+    let schema = serde_json::from_slice::<serde_json::Value>(&contents)?;
+    jsonschema::validator_for(&schema).map_err(|e| format!("Invalid JSON schema: {}", e))?;
+    Ok(schema)
+}
+
+// models occasionally wrap JSON mode answers in a markdown code fence
+fn strip_code_fence(text: &str) -> &str {
+    let trimmed = text.trim();
+    match trimmed.strip_prefix("```") {
+        Some(fenced) => fenced
+            .trim_start_matches("json")
+            .trim_end()
+            .trim_end_matches("```")
+            .trim(),
+        None => trimmed,
+    }
+}
+
+/// Parses `text` as JSON and checks it against `schema`, returning every problem found.
+pub fn validate_json(
+    text: &str,
+    schema: Option<&serde_json::Value>,
+) -> Result<serde_json::Value, Vec<String>> {
+    let value = serde_json::from_str::<serde_json::Value>(strip_code_fence(text))
+        .map_err(|e| vec![format!("Not valid JSON: {}", e)])?;
+
+    if let Some(schema) = schema {
+        let validator = jsonschema::validator_for(schema).map_err(|e| vec![e.to_string()])?;
+        let errors = validator
+            .iter_errors(&value)
+            .map(|error| match error.instance_path().to_string() {
+                path if path.is_empty() => error.to_string(),
+                path => format!("{}: {}", path, error),
+            })
+            .collect::<Vec<String>>();
+
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+    }
+
+    Ok(value)
+}
+
+fn repair_prompt(errors: &[String]) -> String {
+    format!(
+        "Your previous answer failed validation:\n- {}\nReply with only the corrected JSON.",
+        errors.join("\n- ")
+    )
+}
+
+/// Runs the conversation and validates the final answer, re-prompting the model with the
+/// validation errors when `repair` is enabled.
+pub async fn run_json_conversation(
+    mut request: ChatCompletionRequest,
+    registry: &ToolRegistry,
+    output: &JsonOutput,
+    sender: mpsc::Sender<StreamEvent>,
+    logger: &Logger,
+) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    let mut attempt = 0;
+
+    loop {
+        let messages =
+            tools::run_conversation(request.clone(), registry, sender.clone(), logger).await?;
+        let answer = messages
+            .last()
+            .map(|message| message.content.text())
+            .unwrap_or_default();
+
+        let errors = match validate_json(&answer, output.schema.as_ref()) {
+            Ok(value) => return Ok(value),
+            Err(errors) => errors,
+        };
+
+        logger
+            .log_error(format!("JSON validation failed: {}", errors.join("; ")))
+            .await;
+
+        if !output.repair || attempt >= MAX_REPAIR_ATTEMPTS {
+            return Err(format!("Answer failed JSON validation: {}", errors.join("; ")).into());
+        }
+        attempt += 1;
+
+        _ = sender
+            .send(StreamEvent::Token(format!(
+                "\n\n[repair {}/{}] {}\n\n",
+                attempt,
+                MAX_REPAIR_ATTEMPTS,
+                errors.join("; ")
+            )))
+            .await;
+
+        request.messages = messages;
+        request
+            .messages
+            .push(ChatMessage::user(repair_prompt(&errors)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn city_schema() -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "city": {"type": "string"},
+                "population": {"type": "integer", "minimum": 0}
+            },
+            "required": ["city", "population"],
+            "additionalProperties": false
+        })
+    }
+
+    #[test]
+    fn validate_json_accepts_valid_and_fenced_answers() {
+        let schema = city_schema();
+
+        assert_eq!(
+            validate_json(r#"{"city": "Turku", "population": 200000}"#, Some(&schema)),
+            Ok(serde_json::json!({"city": "Turku", "population": 200000}))
+        );
+        assert_eq!(
+            validate_json(
+                "```json\n{\"city\": \"Oulu\", \"population\": 1}\n```",
+                Some(&schema)
+            ),
+            Ok(serde_json::json!({"city": "Oulu", "population": 1}))
+        );
+        assert_eq!(validate_json("[1, 2]", None), Ok(serde_json::json!([1, 2])));
+    }
+
+    #[test]
+    fn validate_json_reports_parse_errors() {
+        let errors = validate_json("{\"city\": ", None).unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("Not valid JSON"));
+    }
+
+    #[test]
+    fn validate_json_reports_schema_errors() {
+        let schema = city_schema();
+        let errors = validate_json(
+            r#"{"city": "Turku", "population": -1, "mayor": "?"}"#,
+            Some(&schema),
+        )
+        .unwrap_err();
+
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().any(|error| error.starts_with("/population:")));
+        assert!(errors.iter().any(|error| error.contains("mayor")));
+    }
+
+    #[test]
+    fn response_format_serializes_for_the_api() {
+        let schema_output = JsonOutput {
+            schema: Some(city_schema()),
+            repair: false,
+        };
+        let object_output = JsonOutput {
+            schema: None,
+            repair: false,
+        };
+
+        let schema_format = serde_json::to_value(schema_output.response_format("city")).unwrap();
+        assert_eq!(schema_format["type"], "json_schema");
+        assert_eq!(schema_format["json_schema"]["name"], "city");
+        assert_eq!(schema_format["json_schema"]["strict"], true);
+        assert_eq!(schema_format["json_schema"]["schema"], city_schema());
+
+        assert_eq!(
+            serde_json::to_value(object_output.response_format("ignored")).unwrap(),
+            serde_json::json!({"type": "json_object"})
+        );
+    }
+}
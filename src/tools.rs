@@ -0,0 +1,474 @@
+use crate::{
+    client,
+    logger::Logger,
+    mcp::{McpAllowList, McpClient},
+    models::{ChatCompletionRequest, ChatMessage, FunctionDefinition, ToolCall, ToolDefinition},
+    stream::{StreamEvent, ToolApproval},
+};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+use tokio::sync::{mpsc, oneshot};
+
+// guards against a model that keeps calling tools forever
+const MAX_TOOL_ROUNDS: usize = 8;
+const MAX_TOOL_OUTPUT_BYTES: usize = 64 * 1024;
+const MAX_TOOL_NAME_LENGTH: usize = 64;
+
+#[derive(Debug, Clone)]
+enum ToolHandler {
+    ReadFile,
+    ListDirectory,
+    Mcp {
+        client: Arc<McpClient>,
+        tool_name: String,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Tool {
+    pub definition: ToolDefinition,
+    pub requires_approval: bool,
+    handler: ToolHandler,
+}
+
+/// Tools the model is allowed to call during a conversation.
+#[derive(Debug, Clone, Default)]
+pub struct ToolRegistry {
+    tools: Vec<Tool>,
+}
+
+fn function_tool(name: &str, description: &str, parameters: serde_json::Value) -> ToolDefinition {
+    ToolDefinition {
+        r#type: "function".to_string(),
+        function: FunctionDefinition {
+            name: name.to_string(),
+            description: description.to_string(),
+            parameters,
+        },
+    }
+}
+
+fn path_parameters(description: &str) -> serde_json::Value {
+    serde_json::json!({
+        "type": "object",
+        "properties": {
+            "path": {"type": "string", "description": description}
+        },
+        "required": ["path"]
+    })
+}
+
+fn path_argument(call: &ToolCall) -> Result<String, String> {
+    let arguments = serde_json::from_str::<serde_json::Value>(&call.function.arguments)
+        .map_err(|e| format!("Invalid arguments for {}: {}", call.function.name, e))?;
+
+    arguments
+        .get("path")
+        .and_then(|path| path.as_str())
+        .map(str::to_string)
+        .ok_or_else(|| format!("Missing \"path\" argument for {}", call.function.name))
+}
+
+// function names may only contain [a-zA-Z0-9_-] and be at most 64 characters
+fn mcp_tool_name(server: &str, tool: &str) -> String {
+    format!("{}__{}", server, tool)
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(MAX_TOOL_NAME_LENGTH)
+        .collect()
+}
+
+// another name for a tool whose sanitized name is taken, e.g. `a_`/`b` and `a`/`_b`
+fn disambiguated_tool_name(name: &str, server: &str, tool: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    (server, tool).hash(&mut hasher);
+    let suffix = format!("_{:08x}", hasher.finish() as u32);
+    let base = name
+        .chars()
+        .take(MAX_TOOL_NAME_LENGTH - suffix.len())
+        .collect::<String>();
+    format!("{}{}", base, suffix)
+}
+
+fn truncate_output(mut output: String) -> String {
+    if output.len() > MAX_TOOL_OUTPUT_BYTES {
+        let mut end = MAX_TOOL_OUTPUT_BYTES;
+        while !output.is_char_boundary(end) {
+            end -= 1;
+        }
+        output.truncate(end);
+        output.push_str("\n[truncated]");
+    }
+    output
+}
+
+async fn read_file(path: &str) -> Result<String, String> {
+    let bytes = tokio::fs::read(path)
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    Ok(truncate_output(String::from_utf8_lossy(&bytes).to_string()))
+}
+
+async fn list_directory(path: &str) -> Result<String, String> {
+    let mut entries = tokio::fs::read_dir(path)
+        .await
+        .map_err(|e| format!("Failed to list {}: {}", path, e))?;
+
+    let mut names = Vec::new();
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .map_err(|e| format!("Failed to list {}: {}", path, e))?
+    {
+        let name = entry.file_name().to_string_lossy().to_string();
+        match entry.file_type().await {
+            Ok(file_type) if file_type.is_dir() => names.push(format!("{}/", name)),
+            _ => names.push(name),
+        }
+    }
+    names.sort();
+
+    Ok(truncate_output(names.join("\n")))
+}
+
+impl ToolRegistry {
+    /// Local tools shipped with the CLI, every call still needs the user's approval.
+    pub fn builtin() -> Self {
+        ToolRegistry {
+            tools: vec![
+                Tool {
+                    definition: function_tool(
+                        "read_file",
+                        "Read a UTF-8 text file from the user's machine",
+                        path_parameters("Path of the file, relative to the working directory"),
+                    ),
+                    requires_approval: true,
+                    handler: ToolHandler::ReadFile,
+                },
+                Tool {
+                    definition: function_tool(
+                        "list_directory",
+                        "List the entries of a directory on the user's machine",
+                        path_parameters("Path of the directory, relative to the working directory"),
+                    ),
+                    requires_approval: true,
+                    handler: ToolHandler::ListDirectory,
+                },
+            ],
+        }
+    }
+
+    fn has_tool(&self, name: &str) -> bool {
+        self.tools
+            .iter()
+            .any(|tool| tool.definition.function.name == name)
+    }
+
+    /// Registers every tool exposed by an MCP server as `<server>__<tool>`. A name that
+    /// is already taken gets a hash suffix so each name calls exactly one tool.
+    pub async fn register_mcp_server(
+        &mut self,
+        client: Arc<McpClient>,
+        allow_list: &McpAllowList,
+        logger: &Logger,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        for mcp_tool in client.list_tools().await? {
+            let mut exposed_name = mcp_tool_name(&client.name, &mcp_tool.name);
+            if self.has_tool(&exposed_name) {
+                let renamed = disambiguated_tool_name(&exposed_name, &client.name, &mcp_tool.name);
+                if self.has_tool(&renamed) {
+                    logger
+                        .log_warn(format!(
+                            "MCP tool {}/{} skipped, its name {} is already taken",
+                            client.name, mcp_tool.name, exposed_name
+                        ))
+                        .await;
+                    continue;
+                }
+                logger
+                    .log_warn(format!(
+                        "MCP tool {}/{} renamed to {}, {} is already taken",
+                        client.name, mcp_tool.name, renamed, exposed_name
+                    ))
+                    .await;
+                exposed_name = renamed;
+            }
+            let description = mcp_tool.description.clone().unwrap_or_else(|| {
+                format!("{} tool from the {} MCP server", mcp_tool.name, client.name)
+            });
+
+            self.tools.push(Tool {
+                definition: function_tool(&exposed_name, &description, mcp_tool.input_schema),
+                requires_approval: !allow_list.allows(&client.name, &mcp_tool.name),
+                handler: ToolHandler::Mcp {
+                    client: client.clone(),
+                    tool_name: mcp_tool.name,
+                },
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Adds the tools of all configured MCP servers, failing servers are logged and skipped.
+    pub async fn with_mcp_servers(mut self, logger: &Logger) -> Self {
+        let allow_list = crate::mcp::get_allow_list().await;
+
+        for client in crate::mcp::connect_configured_servers(logger).await {
+            let server_name = client.name.clone();
+            if let Err(e) = self.register_mcp_server(client, &allow_list, logger).await {
+                logger
+                    .log_error(format!(
+                        "MCP server {} tool discovery failed: {}",
+                        server_name, e
+                    ))
+                    .await;
+            }
+        }
+
+        self
+    }
+
+    /// Unknown tools always need approval, they fail anyway once allowed.
+    pub fn requires_approval(&self, call: &ToolCall) -> bool {
+        self.tools
+            .iter()
+            .find(|tool| tool.definition.function.name == call.function.name)
+            .map(|tool| tool.requires_approval)
+            .unwrap_or(true)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tools.is_empty()
+    }
+
+    pub fn definitions(&self) -> Vec<ToolDefinition> {
+        self.tools
+            .iter()
+            .map(|tool| tool.definition.clone())
+            .collect()
+    }
+
+    /// Runs the registered tool for `call`, errors are returned as text for the model.
+    pub async fn call(&self, call: &ToolCall) -> Result<String, String> {
+        let tool = self
+            .tools
+            .iter()
+            .find(|tool| tool.definition.function.name == call.function.name)
+            .ok_or_else(|| format!("Unknown tool: {}", call.function.name))?;
+
+        match &tool.handler {
+            ToolHandler::ReadFile => read_file(&path_argument(call)?).await,
+            ToolHandler::ListDirectory => list_directory(&path_argument(call)?).await,
+            ToolHandler::Mcp { client, tool_name } => {
+                let arguments = serde_json::from_str::<serde_json::Value>(&call.function.arguments)
+                    .map_err(|e| format!("Invalid arguments for {}: {}", call.function.name, e))?;
+
+                client
+                    .call_tool(tool_name, arguments)
+                    .await
+                    .map_err(|e| format!("MCP call {} failed: {}", call.function.name, e))?
+                    .map(truncate_output)
+            }
+        }
+    }
+}
+
+async fn request_approval(sender: &mpsc::Sender<StreamEvent>, call: &ToolCall) -> bool {
+    let (responder, response) = oneshot::channel();
+    let approval = ToolApproval {
+        call: call.clone(),
+        responder,
+    };
+
+    if sender
+        .send(StreamEvent::ApprovalRequest(approval))
+        .await
+        .is_err()
+    {
+        return false;
+    }
+
+    // a dropped responder means the UI closed without answering
+    response.await.unwrap_or(false)
+}
+
+/// Streams completions until the model stops calling tools.
+///
+/// Each tool call is shown to the user for approval, run through `registry` and sent back
+/// as a `tool` message. Returns the full message history.
+pub async fn run_conversation(
+    mut request: ChatCompletionRequest,
+    registry: &ToolRegistry,
+    sender: mpsc::Sender<StreamEvent>,
+    logger: &Logger,
+) -> Result<Vec<ChatMessage>, Box<dyn std::error::Error>> {
+    if !registry.is_empty() {
+        request.tools = registry.definitions();
+        request.tool_choice = Some("auto".to_string());
+    }
+
+    for _ in 0..MAX_TOOL_ROUNDS {
+        let outcome = client::call_mistral_completions(&request, sender.clone(), logger).await?;
+        let tool_calls = outcome.tool_calls.clone();
+        request
+            .messages
+            .push(ChatMessage::assistant(outcome.content, outcome.tool_calls));
+
+        if tool_calls.is_empty() {
+            return Ok(request.messages);
+        }
+
+        for call in tool_calls {
+            _ = sender.send(StreamEvent::ToolCall(call.clone())).await;
+
+            let approved =
+                !registry.requires_approval(&call) || request_approval(&sender, &call).await;
+            let result = if approved {
+                registry.call(&call).await
+            } else {
+                Err("The user declined this tool call".to_string())
+            };
+            let content = result.unwrap_or_else(|e| format!("Error: {}", e));
+
+            _ = sender
+                .send(StreamEvent::ToolResult {
+                    name: call.function.name.clone(),
+                    content: content.clone(),
+                })
+                .await;
+            request.messages.push(ChatMessage::tool(&call, content));
+        }
+    }
+
+    logger
+        .log_error(format!(
+            "Stopped after {} tool call rounds without a final answer",
+            MAX_TOOL_ROUNDS
+        ))
+        .await;
+
+    Ok(request.messages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::FunctionCall;
+
+    fn tool_call(name: &str, arguments: &str) -> ToolCall {
+        ToolCall {
+            id: "call_0".to_string(),
+            r#type: "function".to_string(),
+            function: FunctionCall {
+                name: name.to_string(),
+                arguments: arguments.to_string(),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn builtin_read_file() {
+        let registry = ToolRegistry::builtin();
+        let content = registry
+            .call(&tool_call("read_file", r#"{"path": "Cargo.toml"}"#))
+            .await
+            .unwrap();
+
+        assert!(content.contains("name = \"jwekke-ai-cli\""));
+    }
+
+    #[tokio::test]
+    async fn builtin_list_directory() {
+        let registry = ToolRegistry::builtin();
+        let content = registry
+            .call(&tool_call("list_directory", r#"{"path": "src"}"#))
+            .await
+            .unwrap();
+
+        assert!(content.lines().any(|line| line == "tools.rs"));
+    }
+
+    #[tokio::test]
+    async fn unknown_tool_and_bad_arguments_are_errors() {
+        let registry = ToolRegistry::builtin();
+
+        assert_eq!(
+            registry.call(&tool_call("delete_file", "{}")).await,
+            Err("Unknown tool: delete_file".to_string())
+        );
+        assert_eq!(
+            registry.call(&tool_call("read_file", "{}")).await,
+            Err("Missing \"path\" argument for read_file".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn mcp_tools_are_registered_and_routed() {
+        let client = Arc::new(crate::mcp::tests::fake_mcp_client("fake.server").await);
+        let allow_list = McpAllowList::new(&["fake.server/echo".to_string()]);
+        let mut registry = ToolRegistry::default();
+        registry
+            .register_mcp_server(client, &allow_list, &Logger::new().await)
+            .await
+            .unwrap();
+
+        let names = registry
+            .definitions()
+            .into_iter()
+            .map(|definition| definition.function.name)
+            .collect::<Vec<String>>();
+        assert_eq!(names, vec!["fake_server__echo", "fake_server__fail"]);
+
+        let echo = tool_call("fake_server__echo", r#"{"text": "terve"}"#);
+        let fail = tool_call("fake_server__fail", "{}");
+        assert!(!registry.requires_approval(&echo));
+        assert!(registry.requires_approval(&fail));
+        assert_eq!(registry.call(&echo).await, Ok("terve".to_string()));
+        assert_eq!(registry.call(&fail).await, Err("tool failed".to_string()));
+    }
+
+    #[tokio::test]
+    async fn colliding_mcp_tool_names_are_disambiguated() {
+        let logger = Logger::new().await;
+        // both servers sanitize to `a_b`, so their tools would share the same names
+        let first = Arc::new(crate::mcp::tests::fake_mcp_client("a.b").await);
+        let second = Arc::new(crate::mcp::tests::fake_mcp_client("a_b").await);
+        let allow_list = McpAllowList::new(&["a.b/echo".to_string()]);
+        let mut registry = ToolRegistry::default();
+        for client in [first, second] {
+            registry
+                .register_mcp_server(client, &allow_list, &logger)
+                .await
+                .unwrap();
+        }
+
+        let names = registry
+            .definitions()
+            .into_iter()
+            .map(|definition| definition.function.name)
+            .collect::<Vec<String>>();
+        assert_eq!(names.len(), 4);
+        assert_eq!(names[0], "a_b__echo");
+        assert!(names[2].starts_with("a_b__echo_"));
+        assert!(names.iter().all(|name| name.len() <= MAX_TOOL_NAME_LENGTH));
+
+        // only the allow-listed server's tool runs without approval
+        assert!(!registry.requires_approval(&tool_call(&names[0], "{}")));
+        assert!(registry.requires_approval(&tool_call(&names[2], "{}")));
+
+        let long = "x".repeat(MAX_TOOL_NAME_LENGTH);
+        let renamed = disambiguated_tool_name(&long, "x", "y");
+        assert_eq!(renamed.len(), MAX_TOOL_NAME_LENGTH);
+        assert_ne!(renamed, disambiguated_tool_name(&long, "x", "z"));
+    }
+}
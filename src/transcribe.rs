@@ -0,0 +1,170 @@
+use crate::models::{TranscriptionRequest, TranscriptionResponse, TranscriptionSegment};
+use std::path::Path;
+
+pub const DEFAULT_TRANSCRIPTION_MODEL: &str = "voxtral-mini-latest";
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum TranscriptFormat {
+    /// Plain text, one timestamped line per segment with `--timestamps`
+    Text,
+    /// SubRip subtitles
+    Srt,
+    /// The full response with language and segments
+    Json,
+}
+
+pub fn audio_mime_type(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+    match extension.as_str() {
+        "wav" => Some("audio/wav"),
+        "mp3" => Some("audio/mpeg"),
+        "m4a" | "mp4" => Some("audio/mp4"),
+        "flac" => Some("audio/flac"),
+        "ogg" | "oga" => Some("audio/ogg"),
+        "webm" => Some("audio/webm"),
+        _ => None,
+    }
+}
+
+/// Reads a local recording into a transcription request.
+pub async fn read_audio_request(
+    path: &Path,
+    model: String,
+    language: Option<String>,
+    segment_timestamps: bool,
+) -> Result<TranscriptionRequest, Box<dyn std::error::Error>> {
+    let mime_type = audio_mime_type(path).ok_or_else(|| {
+        format!(
+            "Unsupported audio type {}, use wav, mp3, m4a, flac, ogg or webm",
+            path.display()
+        )
+    })?;
+    let audio = tokio::fs::read(path)
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    Ok(TranscriptionRequest {
+        model,
+        file_name: path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "audio".to_string()),
+        mime_type: mime_type.to_string(),
+        audio,
+        language,
+        segment_timestamps,
+    })
+}
+
+// `separator` is ',' for SRT and '.' for the text output
+fn timestamp(seconds: f64, separator: char) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        separator,
+        millis % 1000
+    )
+}
+
+pub fn to_srt(segments: &[TranscriptionSegment]) -> String {
+    segments
+        .iter()
+        .enumerate()
+        .map(|(index, segment)| {
+            format!(
+                "{}\n{} --> {}\n{}\n",
+                index + 1,
+                timestamp(segment.start, ','),
+                timestamp(segment.end, ','),
+                segment.text.trim()
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn to_timestamped_text(segments: &[TranscriptionSegment]) -> String {
+    segments
+        .iter()
+        .map(|segment| {
+            format!(
+                "[{} --> {}] {}",
+                timestamp(segment.start, '.'),
+                timestamp(segment.end, '.'),
+                segment.text.trim()
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+pub fn format_transcript(
+    response: &TranscriptionResponse,
+    format: TranscriptFormat,
+) -> Result<String, Box<dyn std::error::Error>> {
+    match format {
+        TranscriptFormat::Text if response.segments.is_empty() => {
+            Ok(response.text.trim().to_string())
+        }
+        TranscriptFormat::Text => Ok(to_timestamped_text(&response.segments)),
+        TranscriptFormat::Srt if response.segments.is_empty() => {
+            Err("The transcription has no segments to write as SRT".into())
+        }
+        TranscriptFormat::Srt => Ok(to_srt(&response.segments)),
+        TranscriptFormat::Json => Ok(serde_json::to_string_pretty(response)?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transcription() -> TranscriptionResponse {
+        serde_json::from_value(serde_json::json!({
+            "model": "voxtral-mini-2507",
+            "text": " Hyvää huomenta. Kahvi on valmis.",
+            "language": "fi",
+            "segments": [
+                {"text": " Hyvää huomenta.", "start": 0.0, "end": 1.52},
+                {"text": " Kahvi on valmis.", "start": 1.52, "end": 3723.4}
+            ],
+            "usage": {"prompt_audio_seconds": 4, "prompt_tokens": 4, "total_tokens": 120}
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn srt_and_text_output() {
+        let response = transcription();
+
+        assert_eq!(
+            format_transcript(&response, TranscriptFormat::Srt).unwrap(),
+            "1\n00:00:00,000 --> 00:00:01,520\nHyvää huomenta.\n\n\
+             2\n00:00:01,520 --> 01:02:03,400\nKahvi on valmis.\n"
+        );
+        assert_eq!(
+            format_transcript(&response, TranscriptFormat::Text).unwrap(),
+            "[00:00:00.000 --> 00:00:01.520] Hyvää huomenta.\n\
+             [00:00:01.520 --> 01:02:03.400] Kahvi on valmis."
+        );
+    }
+
+    #[test]
+    fn transcripts_without_segments() {
+        let response = TranscriptionResponse {
+            segments: Vec::new(),
+            ..transcription()
+        };
+
+        assert_eq!(
+            format_transcript(&response, TranscriptFormat::Text).unwrap(),
+            "Hyvää huomenta. Kahvi on valmis."
+        );
+        assert!(format_transcript(&response, TranscriptFormat::Srt).is_err());
+        assert_eq!(audio_mime_type(Path::new("memo.M4A")), Some("audio/mp4"));
+        assert_eq!(audio_mime_type(Path::new("memo.txt")), None);
+    }
+}
@@ -1,43 +1,241 @@
-use crate::{client::list_mistral_models, config::save_model_name, logger::Logger, ui};
+use crate::{
+    client::list_mistral_models,
+    clipboard::{self, CodeBlock},
+    config::save_model_name,
+    index::Citation,
+    logger::{LogLevel, Logger},
+    logs::{self, LogEntry},
+    markdown,
+    models::{ModelCapability, UsageInfo},
+    search::{self, Search},
+    stream::{StreamEvent, ToolApproval},
+    ui,
+};
 use crossterm::{
+    event::{DisableMouseCapture, EnableMouseCapture, KeyCode, KeyModifiers, MouseEventKind},
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
-use inquire::{InquireError, Select};
+use inquire::{Confirm, InquireError, Select};
 use ratatui::{
     Terminal,
     backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Margin, Rect},
     style::{Color, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Paragraph, Wrap},
+    widgets::{
+        Block, Borders, Clear, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState, Wrap,
+    },
+};
+use std::{
+    io::{self, Stdout, Write},
+    time::{Duration, Instant},
 };
-use std::io::{self, Stdout};
-use tokio::sync::mpsc;
+use tokio::sync::mpsc::{self, error::TryRecvError};
+
+/// One model's streamed answer together with its timing and token usage.
+pub struct StreamPane {
+    pub model: String,
+    pub content: String,
+    pub usage: Option<UsageInfo>,
+    pub chunk_count: usize,
+    pub started_at: Instant,
+    pub first_token_at: Option<Instant>,
+    pub finished_at: Option<Instant>,
+    pub citations: Vec<Citation>,
+    pending_approval: Option<ToolApproval>,
+    receiver: mpsc::Receiver<StreamEvent>,
+}
+
+const TOOL_RESULT_PREVIEW_CHARS: usize = 200;
+const ERROR_PANEL_ROWS: usize = 3;
+const MOUSE_SCROLL_LINES: isize = 3;
+const NOTICE_DURATION: Duration = Duration::from_secs(3);
+const MAX_CITATION_ROWS: usize = 8;
+
+impl StreamPane {
+    pub fn new(model: String, receiver: mpsc::Receiver<StreamEvent>) -> Self {
+        StreamPane {
+            model,
+            content: String::new(),
+            usage: None,
+            chunk_count: 0,
+            started_at: Instant::now(),
+            first_token_at: None,
+            finished_at: None,
+            citations: Vec::new(),
+            pending_approval: None,
+            receiver,
+        }
+    }
+
+    /// Sources listed under the answer, e.g. the chunks retrieved for `ai ask`.
+    pub fn with_citations(mut self, citations: Vec<Citation>) -> Self {
+        self.citations = citations;
+        self
+    }
+
+    pub fn citation_lines(&self) -> Vec<String> {
+        self.citations
+            .iter()
+            .map(|citation| {
+                format!(
+                    "[{}] {} (lines {}-{})",
+                    citation.number,
+                    citation.uri(),
+                    citation.start_line,
+                    citation.end_line
+                )
+            })
+            .collect()
+    }
+
+    pub fn apply_event(&mut self, event: StreamEvent) {
+        match event {
+            StreamEvent::Token(token) => {
+                if self.first_token_at.is_none() {
+                    self.first_token_at = Some(Instant::now());
+                }
+                self.chunk_count += 1;
+                self.content.push_str(&token);
+            }
+            StreamEvent::Usage(usage) => self.usage = Some(usage),
+            StreamEvent::ToolCall(call) => {
+                self.content.push_str(&format!(
+                    "\n[tool call] {}({})\n",
+                    call.function.name, call.function.arguments
+                ));
+            }
+            StreamEvent::ToolResult { name, content } => {
+                let mut preview = content
+                    .chars()
+                    .take(TOOL_RESULT_PREVIEW_CHARS)
+                    .collect::<String>();
+                if preview.len() < content.len() {
+                    preview.push('…');
+                }
+                self.content
+                    .push_str(&format!("[tool result] {}: {}\n\n", name, preview));
+            }
+            StreamEvent::ApprovalRequest(approval) => self.pending_approval = Some(approval),
+        }
+    }
+
+    /// Answers the pending tool approval, returns false when nothing was waiting.
+    pub fn answer_approval(&mut self, approved: bool) -> bool {
+        match self.pending_approval.take() {
+            Some(approval) => {
+                _ = approval.responder.send(approved);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // pulls every pending event without blocking the render loop
+    fn drain_events(&mut self) {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(event) => self.apply_event(event),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    if self.finished_at.is_none() {
+                        self.finished_at = Some(Instant::now());
+                    }
+                    break;
+                }
+            }
+        }
+    }
+
+    pub fn first_token_latency(&self) -> Option<Duration> {
+        self.first_token_at
+            .map(|first_token_at| first_token_at - self.started_at)
+    }
+
+    pub fn total_latency(&self) -> Option<Duration> {
+        self.finished_at
+            .map(|finished_at| finished_at - self.started_at)
+    }
+
+    /// Completion tokens reported by the API, or the number of streamed chunks until the
+    /// final usage block arrives.
+    pub fn completion_tokens(&self) -> u64 {
+        self.usage
+            .as_ref()
+            .map(|usage| usage.completion_tokens)
+            .unwrap_or(self.chunk_count as u64)
+    }
+
+    pub fn stats_text(&self) -> String {
+        let first_token = self
+            .first_token_latency()
+            .map(|latency| format!("{}ms", latency.as_millis()))
+            .unwrap_or_else(|| "-".to_string());
+        let total = match self.total_latency() {
+            Some(latency) => format!("{:.1}s", latency.as_secs_f64()),
+            None => format!("{:.1}s…", self.started_at.elapsed().as_secs_f64()),
+        };
+        let prompt_tokens = self
+            .usage
+            .as_ref()
+            .map(|usage| usage.prompt_tokens.to_string())
+            .unwrap_or_else(|| "-".to_string());
+
+        format!(
+            "first token {} · total {} · {} in / {} out tokens",
+            first_token,
+            total,
+            prompt_tokens,
+            self.completion_tokens()
+        )
+    }
+}
+
+// picker entry, models created by our own fine-tuning jobs are tagged
+struct ModelOption<'a> {
+    id: &'a str,
+    fine_tuned: bool,
+}
+
+impl std::fmt::Display for ModelOption<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.fine_tuned {
+            write!(f, "{} [fine-tuned]", self.id)
+        } else {
+            write!(f, "{}", self.id)
+        }
+    }
+}
 
 /// Displays a list of available Mistral AI models and prompts the user to select one.
 ///
 /// # Returns
 /// A `Result` containing the selected model's name or an error.
 pub async fn select_mistral_model(logger: &Logger) -> Result<String, Box<dyn std::error::Error>> {
-    let models = list_mistral_models(&logger).await.map_err(|e| {
-        _ = ui::restore_terminal();
-        panic!("Failed to load models: {}", e);
-    })?;
+    let models = list_mistral_models(logger, ModelCapability::CompletionChat)
+        .await
+        .map_err(|e| {
+            _ = ui::restore_terminal();
+            panic!("Failed to load models: {}", e);
+        })?;
 
     let options = models
         .iter()
-        .map(|model| model.id.as_str())
-        .collect::<Vec<&str>>();
+        .map(|model| ModelOption {
+            id: model.id.as_str(),
+            fine_tuned: model.job.is_some(),
+        })
+        .collect::<Vec<ModelOption>>();
 
-    let ans: Result<&str, InquireError> = Select::new("Select Mistral Model", options)
+    let ans: Result<ModelOption, InquireError> = Select::new("Select Mistral Model", options)
         .with_page_size(15)
         .prompt();
 
     let selection = match ans {
-        Ok(choice) => choice.to_string(),
+        Ok(choice) => choice.id.to_string(),
         Err(_) => {
-            logger.log_error(format!("Error selecting model")).await;
+            logger.log_error("Error selecting model".to_string()).await;
             return Err("Selection failed".into());
         }
     };
@@ -52,10 +250,21 @@ pub async fn select_mistral_model(logger: &Logger) -> Result<String, Box<dyn std
     Ok(selection)
 }
 
+fn centered_rect(width_percent: u16, height: u16, area: Rect) -> Rect {
+    let width = area.width * width_percent / 100;
+    let height = height.min(area.height);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + (area.height - height) / 2,
+        width,
+        height,
+    }
+}
+
 pub fn setup_terminal() -> io::Result<Terminal<CrosstermBackend<Stdout>>> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
     Terminal::new(CrosstermBackend::new(stdout))
 }
 
@@ -65,22 +274,215 @@ pub fn restore_terminal() -> io::Result<()> {
         io::stdout(),
         crossterm::terminal::Clear(crossterm::terminal::ClearType::All)
     )?;
-    execute!(io::stdout(), LeaveAlternateScreen)?;
+    execute!(io::stdout(), DisableMouseCapture, LeaveAlternateScreen)?;
     Ok(())
 }
 
+/// Prints the answers to the normal screen once the TUI is gone, so they stay in the
+/// scrollback. Markdown is styled with ANSI codes when `styled` is set.
+pub fn print_panes(panes: &[StreamPane], styled: bool) {
+    let answered = panes
+        .iter()
+        .filter(|pane| !pane.content.trim().is_empty())
+        .collect::<Vec<&StreamPane>>();
+
+    for pane in &answered {
+        if answered.len() > 1 {
+            println!("── {} ──", pane.model);
+        }
+        if styled {
+            print!("{}", markdown::to_ansi(&pane.content));
+        } else {
+            println!("{}", pane.content.trim_end_matches('\n'));
+        }
+        if !pane.citations.is_empty() {
+            println!("\nSources:");
+            for line in pane.citation_lines() {
+                println!("{}", line);
+            }
+        }
+        if answered.len() > 1 {
+            println!();
+        }
+    }
+}
+
+/// Prints the stream straight to the terminal without the TUI.
+///
+/// Tokens go to stdout when `echo_tokens` is set, tool activity goes to stderr and tool
+/// approvals are asked on the terminal.
+pub async fn render_plain(mut pane: StreamPane, echo_tokens: bool) -> StreamPane {
+    while let Some(event) = pane.receiver.recv().await {
+        match event {
+            StreamEvent::Token(token) => {
+                if echo_tokens {
+                    print!("{}", token);
+                    _ = io::stdout().flush();
+                }
+                pane.apply_event(StreamEvent::Token(token));
+            }
+            StreamEvent::ToolCall(call) => {
+                eprintln!(
+                    "[tool call] {}({})",
+                    call.function.name, call.function.arguments
+                );
+                pane.apply_event(StreamEvent::ToolCall(call));
+            }
+            StreamEvent::ToolResult { name, content } => {
+                eprintln!("[tool result] {}: {} bytes", name, content.len());
+                pane.apply_event(StreamEvent::ToolResult { name, content });
+            }
+            StreamEvent::ApprovalRequest(approval) => {
+                let question = format!(
+                    "Allow tool call {}({})?",
+                    approval.call.function.name, approval.call.function.arguments
+                );
+                let approved = tokio::task::block_in_place(|| {
+                    Confirm::new(&question).with_default(false).prompt()
+                })
+                .unwrap_or(false);
+                _ = approval.responder.send(approved);
+            }
+            event => pane.apply_event(event),
+        }
+    }
+
+    pane.finished_at = Some(Instant::now());
+    if echo_tokens && !pane.content.ends_with('\n') {
+        println!();
+    }
+    if echo_tokens && !pane.citations.is_empty() {
+        println!("\nSources:");
+        for line in pane.citation_lines() {
+            println!("{}", line);
+        }
+    }
+
+    pane
+}
+
+/// The pane Up/Down and Left/Right act on, switched with Tab.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Focus {
+    Stream,
+    Errors,
+}
+
+/// Scroll position of the token stream. It sticks to the end while new tokens arrive
+/// until the user scrolls up, and returns to following once scrolled back down.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct StreamScroll {
+    offset: usize,
+    max: usize,
+    page: usize,
+    follow: bool,
+}
+
+impl StreamScroll {
+    fn new() -> Self {
+        StreamScroll {
+            offset: 0,
+            max: 0,
+            page: 1,
+            follow: true,
+        }
+    }
+
+    fn scroll_by(&mut self, lines: isize) {
+        self.offset = self.offset.saturating_add_signed(lines).min(self.max);
+        self.follow = self.offset == self.max;
+    }
+
+    fn page_by(&mut self, pages: isize) {
+        self.scroll_by(pages * self.page.max(1) as isize);
+    }
+
+    fn scroll_to_top(&mut self) {
+        self.offset = 0;
+        self.follow = self.max == 0;
+    }
+
+    fn scroll_to_bottom(&mut self) {
+        self.offset = self.max;
+        self.follow = true;
+    }
+
+    /// Clamps to the lines laid out in the last frame, `max` being the wrapped line count
+    /// past the viewport.
+    fn update(&mut self, max: usize, page: usize) {
+        self.max = max;
+        self.page = page;
+        self.offset = if self.follow {
+            max
+        } else {
+            self.offset.min(max)
+        };
+    }
+}
+
+/// The pane and item at `index` when the items of all panes are counted in order.
+fn nth_in_panes<T>(items: &[Vec<T>], index: usize) -> Option<(usize, &T)> {
+    let mut first = 0;
+    for (pane_index, pane_items) in items.iter().enumerate() {
+        if let Some(item) = index
+            .checked_sub(first)
+            .and_then(|local| pane_items.get(local))
+        {
+            return Some((pane_index, item));
+        }
+        first += pane_items.len();
+    }
+    None
+}
+
+fn copy_notice(what: &str, text: &str) -> String {
+    match clipboard::copy(text) {
+        Some(method) => format!(
+            "Copied the {} ({} characters, {})",
+            what,
+            text.chars().count(),
+            method
+        ),
+        None => format!("Could not copy the {}", what),
+    }
+}
+
+fn severity_color(entry: &LogEntry) -> Color {
+    match entry.level {
+        Some(LogLevel::Warn) => Color::Yellow,
+        _ => Color::Red,
+    }
+}
+
+fn key_hint(key: &str) -> Span<'_> {
+    Span::styled(
+        key,
+        Style::default()
+            .fg(Color::Green)
+            .add_modifier(ratatui::style::Modifier::BOLD),
+    )
+}
+
 pub async fn render_ui(
     terminal: &mut Terminal<CrosstermBackend<Stdout>>,
     logger: &Logger,
-    model: String,
     prompt: String,
-    mut token_receiver: mpsc::Receiver<String>,
-) -> io::Result<()> {
-    let mut token_stream = String::new();
+    image_names: Vec<String>,
+    mut panes: Vec<StreamPane>,
+) -> io::Result<Vec<StreamPane>> {
     let mut should_quit = false;
-    let mut scroll_offset = 0;
-    let mut error_scroll_offset = 0;
-    let mut error_horizontal_scroll_offset = 0;
+    let mut scroll = StreamScroll::new();
+    let mut focus = Focus::Stream;
+    let mut search = Search::default();
+    let mut match_count = 0;
+    let mut pane_blocks: Vec<Vec<CodeBlock>> = Vec::new();
+    let mut selected_block: Option<usize> = None;
+    let mut block_jump = false;
+    let mut notice: Option<(String, Instant)> = None;
+    let mut show_errors = true;
+    let mut show_error_detail = false;
+    let mut selected_error = 0;
+    let mut error_horizontal_scroll_offset: usize = 0;
 
     enable_raw_mode()?;
     execute!(
@@ -89,57 +491,181 @@ pub async fn render_ui(
     )?;
 
     while !should_quit {
+        let errors = logger.get_errors().await;
+        let errors_visible = show_errors && !errors.is_empty();
+        if !errors_visible {
+            focus = Focus::Stream;
+            show_error_detail = false;
+        }
+        selected_error = selected_error.min(errors.len().saturating_sub(1));
+
         if crossterm::event::poll(std::time::Duration::from_millis(50))? {
-            if let crossterm::event::Event::Key(key_event) = crossterm::event::read()? {
-                match key_event.code {
-                    crossterm::event::KeyCode::Char('q') => {
+            match crossterm::event::read()? {
+                crossterm::event::Event::Key(key_event) if search.editing => match key_event.code {
+                    KeyCode::Char(c) => search.push(c),
+                    KeyCode::Backspace => search.pop(),
+                    KeyCode::Enter => search.editing = false,
+                    KeyCode::Esc => search.clear(),
+                    _ => {}
+                },
+                crossterm::event::Event::Key(key_event) => match key_event.code {
+                    KeyCode::Char('q') => {
                         should_quit = true;
                     }
-                    crossterm::event::KeyCode::Up => {
-                        if scroll_offset > 0 {
-                            scroll_offset -= 1;
+                    KeyCode::Esc if show_error_detail => {
+                        show_error_detail = false;
+                    }
+                    KeyCode::Esc => search.clear(),
+                    KeyCode::Char(answer @ ('y' | 'n'))
+                        if panes.iter().any(|pane| pane.pending_approval.is_some()) =>
+                    {
+                        if let Some(pane) = panes
+                            .iter_mut()
+                            .find(|pane| pane.pending_approval.is_some())
+                        {
+                            pane.answer_approval(answer == 'y');
                         }
-                        if error_scroll_offset > 0 {
-                            error_scroll_offset -= 1;
+                    }
+                    KeyCode::Char('e') => {
+                        show_errors = !show_errors;
+                    }
+                    KeyCode::Char('/') => search.start(),
+                    KeyCode::Char('n') => search.step(true, match_count),
+                    KeyCode::Char('N') => search.step(false, match_count),
+                    KeyCode::Char(key @ ('[' | ']')) => {
+                        let block_count = pane_blocks.iter().map(Vec::len).sum::<usize>();
+                        if block_count > 0 {
+                            selected_block = Some(match (selected_block, key) {
+                                (None, ']') => 0,
+                                (None, _) => block_count - 1,
+                                (Some(index), ']') => (index + 1) % block_count,
+                                (Some(index), _) => (index + block_count - 1) % block_count,
+                            });
+                            block_jump = true;
                         }
                     }
-                    crossterm::event::KeyCode::Down => {
-                        scroll_offset += 1;
-                        error_scroll_offset += 1;
+                    KeyCode::Char('c') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                        should_quit = true;
                     }
-                    crossterm::event::KeyCode::Left => {
-                        if error_horizontal_scroll_offset > 0 {
-                            error_horizontal_scroll_offset -= 1;
+                    KeyCode::Char('c') if key_event.modifiers == KeyModifiers::NONE => {
+                        // the pane of the selected code block when comparing models
+                        let pane_index = selected_block
+                            .and_then(|index| nth_in_panes(&pane_blocks, index))
+                            .map_or(0, |(pane_index, _)| pane_index);
+                        if let Some(pane) = panes.get(pane_index) {
+                            notice = Some((copy_notice("response", &pane.content), Instant::now()));
                         }
                     }
-                    crossterm::event::KeyCode::Right => {
+                    KeyCode::Char('C')
+                        if key_event.modifiers == KeyModifiers::SHIFT
+                            || key_event.modifiers == KeyModifiers::NONE =>
+                    {
+                        let index = selected_block.unwrap_or(0);
+                        let message = match nth_in_panes(&pane_blocks, index) {
+                            Some((_, block)) => {
+                                selected_block = Some(index);
+                                copy_notice("code block", &block.code)
+                            }
+                            None => "No code blocks to copy".to_string(),
+                        };
+                        notice = Some((message, Instant::now()));
+                    }
+                    KeyCode::Tab if errors_visible => {
+                        focus = match focus {
+                            Focus::Stream => Focus::Errors,
+                            Focus::Errors => Focus::Stream,
+                        };
+                    }
+                    KeyCode::Enter if focus == Focus::Errors => {
+                        show_error_detail = !show_error_detail;
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => match focus {
+                        Focus::Stream => scroll.scroll_by(-1),
+                        Focus::Errors => selected_error = selected_error.saturating_sub(1),
+                    },
+                    KeyCode::Down | KeyCode::Char('j') => match focus {
+                        Focus::Stream => scroll.scroll_by(1),
+                        Focus::Errors => {
+                            selected_error =
+                                (selected_error + 1).min(errors.len().saturating_sub(1))
+                        }
+                    },
+                    KeyCode::PageUp => scroll.page_by(-1),
+                    KeyCode::PageDown => scroll.page_by(1),
+                    KeyCode::Home | KeyCode::Char('g') => match focus {
+                        Focus::Stream => scroll.scroll_to_top(),
+                        Focus::Errors => selected_error = 0,
+                    },
+                    KeyCode::End | KeyCode::Char('G') => match focus {
+                        Focus::Stream => scroll.scroll_to_bottom(),
+                        Focus::Errors => selected_error = errors.len().saturating_sub(1),
+                    },
+                    KeyCode::Left if focus == Focus::Errors => {
+                        error_horizontal_scroll_offset =
+                            error_horizontal_scroll_offset.saturating_sub(1);
+                    }
+                    KeyCode::Right if focus == Focus::Errors => {
                         error_horizontal_scroll_offset += 1;
                     }
                     _ => {}
-                }
+                },
+                crossterm::event::Event::Mouse(mouse_event) => match mouse_event.kind {
+                    MouseEventKind::ScrollUp => scroll.scroll_by(-MOUSE_SCROLL_LINES),
+                    MouseEventKind::ScrollDown => scroll.scroll_by(MOUSE_SCROLL_LINES),
+                    _ => {}
+                },
+                _ => {}
             }
         }
 
         // Handle token stream updates
-        if let Ok(token) = token_receiver.try_recv() {
-            token_stream.push_str(&token);
+        for pane in panes.iter_mut() {
+            pane.drain_events();
         }
 
-        terminal.draw(|f: &mut ratatui::Frame| {
-            let errors = tokio::task::block_in_place(|| {
-                tokio::runtime::Handle::current().block_on(logger.get_errors())
-            });
+        let search_regex = search.regex();
+        let pane_matches = panes
+            .iter()
+            .map(|pane| search::find_matches(&pane.content, search_regex.as_ref()))
+            .collect::<Vec<_>>();
+        match_count = pane_matches.iter().map(Vec::len).sum();
+        let current_match = search.current.min(match_count.saturating_sub(1));
+        pane_blocks = panes
+            .iter()
+            .map(|pane| clipboard::code_blocks(&pane.content))
+            .collect();
 
+        // pane, byte offset and whether to center it rather than show it at the top
+        let mut jump_to = None;
+        if search.jump {
+            search.jump = false;
+            jump_to = nth_in_panes(&pane_matches, current_match)
+                .map(|(pane_index, hit)| (pane_index, hit.start, true));
+        }
+        if block_jump {
+            block_jump = false;
+            jump_to = selected_block
+                .and_then(|index| nth_in_panes(&pane_blocks, index))
+                .map(|(pane_index, block)| (pane_index, block.start, false));
+        }
+        if notice
+            .as_ref()
+            .is_some_and(|(_, shown_at)| shown_at.elapsed() > NOTICE_DURATION)
+        {
+            notice = None;
+        }
+
+        terminal.draw(|f: &mut ratatui::Frame| {
             let size = f.size();
-            let constraints: Vec<Constraint> = if errors.is_empty() {
+            let constraints: Vec<Constraint> = if errors_visible {
                 vec![
+                    Constraint::Length(ERROR_PANEL_ROWS.min(errors.len()) as u16 + 2),
                     Constraint::Length(5),
                     Constraint::Min(1),
                     Constraint::Length(3),
                 ]
             } else {
                 vec![
-                    Constraint::Length(5),
                     Constraint::Length(5),
                     Constraint::Min(1),
                     Constraint::Length(3),
@@ -151,36 +677,67 @@ pub async fn render_ui(
                 .constraints(constraints.clone())
                 .split(size);
 
-            if !errors.is_empty() {
+            if errors_visible {
                 let errors_text: Vec<Line> = errors
                     .iter()
-                    .map(|error| {
-                        Line::from(Span::styled(
-                            error.as_str(),
-                            Style::default().fg(Color::Red),
-                        ))
+                    .enumerate()
+                    .map(|(index, entry)| {
+                        let mut style = Style::default().fg(severity_color(entry));
+                        if focus == Focus::Errors && index == selected_error {
+                            style = style.add_modifier(ratatui::style::Modifier::REVERSED);
+                        }
+                        Line::from(Span::styled(logs::format_summary(entry), style))
                     })
                     .collect();
+                let warning_count = errors
+                    .iter()
+                    .filter(|entry| entry.level == Some(LogLevel::Warn))
+                    .count();
+                let title = format!(
+                    "Errors · {} errors, {} warnings",
+                    errors.len() - warning_count,
+                    warning_count
+                );
+                let border_style = if focus == Focus::Errors {
+                    Style::default().fg(Color::Cyan)
+                } else {
+                    Style::default()
+                };
+                // keeps the selected row in view
+                let first_row = selected_error.saturating_sub(ERROR_PANEL_ROWS - 1);
                 let errors_paragraph = Paragraph::new(errors_text)
-                    .block(Block::default().borders(Borders::ALL).title("Errors"))
-                    .scroll((
-                        error_scroll_offset as u16,
-                        error_horizontal_scroll_offset as u16,
-                    ));
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .border_style(border_style)
+                            .title(title),
+                    )
+                    .scroll((first_row as u16, error_horizontal_scroll_offset as u16));
                 f.render_widget(errors_paragraph, chunks[0]);
             }
 
-            let model_prompt_index = if errors.is_empty() { 0 } else { 1 };
-            let model_prompt_text = vec![
+            let model_prompt_index = if errors_visible { 1 } else { 0 };
+            let models = panes
+                .iter()
+                .map(|pane| pane.model.as_str())
+                .collect::<Vec<&str>>()
+                .join(", ");
+            let mut model_prompt_text = vec![
                 Line::from(vec![
                     Span::styled("Model: ", Style::default().fg(Color::Yellow)),
-                    Span::styled(model.as_str(), Style::default().fg(Color::Green)),
+                    Span::styled(models, Style::default().fg(Color::Green)),
                 ]),
                 Line::from(vec![
                     Span::styled("Prompt: ", Style::default().fg(Color::Yellow)),
                     Span::styled(prompt.as_str(), Style::default().fg(Color::Green)),
                 ]),
             ];
+            if !image_names.is_empty() {
+                model_prompt_text.push(Line::from(vec![
+                    Span::styled("Images: ", Style::default().fg(Color::Yellow)),
+                    Span::styled(image_names.join(", "), Style::default().fg(Color::Green)),
+                ]));
+            }
             let model_prompt_paragraph = Paragraph::new(model_prompt_text).block(
                 Block::default()
                     .borders(Borders::ALL)
@@ -188,43 +745,309 @@ pub async fn render_ui(
             );
             f.render_widget(model_prompt_paragraph, chunks[model_prompt_index]);
 
-            let token_stream_index = if errors.is_empty() { 1 } else { 2 };
-            let token_stream_paragraph = Paragraph::new(token_stream.clone())
-                .block(Block::default().borders(Borders::ALL).title("Token Stream"))
-                .wrap(Wrap { trim: true }) // Enable word wrapping
-                .scroll((scroll_offset as u16, 0)); // Apply scroll offset
-            f.render_widget(token_stream_paragraph, chunks[token_stream_index]);
-            let controls_index = if errors.is_empty() { 2 } else { 3 };
-            let controls_text = Line::from(vec![
-                Span::styled("Press ", Style::default().fg(Color::Yellow)),
-                Span::styled(
-                    "q",
-                    Style::default()
-                        .fg(Color::Green)
-                        .add_modifier(ratatui::style::Modifier::BOLD),
-                ),
-                Span::styled(" to quit. Use ", Style::default().fg(Color::Yellow)),
-                Span::styled(
-                    "↑",
-                    Style::default()
-                        .fg(Color::Green)
-                        .add_modifier(ratatui::style::Modifier::BOLD),
-                ),
-                Span::styled("/", Style::default().fg(Color::Yellow)),
-                Span::styled(
-                    "↓",
-                    Style::default()
-                        .fg(Color::Green)
-                        .add_modifier(ratatui::style::Modifier::BOLD),
-                ),
-                Span::styled(" to scroll.", Style::default().fg(Color::Yellow)),
-            ]);
+            let token_stream_index = if errors_visible { 2 } else { 1 };
+            let pane_chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints(
+                    panes
+                        .iter()
+                        .map(|_| Constraint::Ratio(1, panes.len() as u32))
+                        .collect::<Vec<Constraint>>(),
+                )
+                .split(chunks[token_stream_index]);
+            if let Some((pane_index, position, center)) = jump_to {
+                let viewport = pane_chunks[pane_index].inner(&Margin::new(1, 1));
+                let row = search::wrapped_row(&panes[pane_index].content, position, viewport.width);
+                let context = if center {
+                    viewport.height as usize / 2
+                } else {
+                    0
+                };
+                scroll.offset = row.saturating_sub(context);
+                scroll.follow = false;
+            }
+
+            let mut scroll_max = 0;
+            let mut page = usize::MAX;
+            let mut first_match = 0;
+            for ((pane, pane_chunk), matches) in panes
+                .iter()
+                .zip(pane_chunks.iter())
+                .zip(pane_matches.iter())
+            {
+                let title = if panes.len() > 1 {
+                    format!("Token Stream · {} · {}", pane.model, pane.stats_text())
+                } else {
+                    format!("Token Stream · {}", pane.stats_text())
+                };
+                let citation_lines = pane.citation_lines();
+                let pane_areas = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints(if citation_lines.is_empty() {
+                        vec![Constraint::Min(1)]
+                    } else {
+                        vec![
+                            Constraint::Min(1),
+                            Constraint::Length(
+                                citation_lines.len().min(MAX_CITATION_ROWS) as u16 + 2,
+                            ),
+                        ]
+                    })
+                    .split(*pane_chunk);
+
+                let current = current_match
+                    .checked_sub(first_match)
+                    .filter(|index| *index < matches.len());
+                first_match += matches.len();
+                let token_stream_paragraph =
+                    Paragraph::new(search::highlight(&pane.content, matches, current))
+                        .wrap(Wrap { trim: true }); // Enable word wrapping
+                let viewport = pane_areas[0].inner(&Margin::new(1, 1));
+                let pane_max = token_stream_paragraph
+                    .line_count(viewport.width)
+                    .saturating_sub(viewport.height as usize);
+                scroll_max = scroll_max.max(pane_max);
+                page = page.min(viewport.height as usize);
+                // panes shorter than the longest one stay at their own end
+                let pane_offset = if scroll.follow {
+                    pane_max
+                } else {
+                    scroll.offset.min(pane_max)
+                };
+                let token_stream_paragraph = token_stream_paragraph
+                    .block(Block::default().borders(Borders::ALL).title(title))
+                    .scroll((pane_offset as u16, 0)); // Apply scroll offset
+                f.render_widget(token_stream_paragraph, pane_areas[0]);
+                if pane_max > 0 {
+                    f.render_stateful_widget(
+                        Scrollbar::new(ScrollbarOrientation::VerticalRight),
+                        pane_areas[0].inner(&Margin::new(0, 1)),
+                        &mut ScrollbarState::new(pane_max).position(pane_offset),
+                    );
+                }
+
+                if !citation_lines.is_empty() {
+                    // kept unwrapped so the URIs stay intact for the terminal to link
+                    let citations_paragraph = Paragraph::new(
+                        citation_lines
+                            .into_iter()
+                            .map(|line| {
+                                Line::from(Span::styled(line, Style::default().fg(Color::Cyan)))
+                            })
+                            .collect::<Vec<Line>>(),
+                    )
+                    .block(Block::default().borders(Borders::ALL).title("Sources"));
+                    f.render_widget(citations_paragraph, pane_areas[1]);
+                }
+            }
+            scroll.update(scroll_max, page);
+
+            let controls_index = if errors_visible { 3 } else { 2 };
+            let hint = Style::default().fg(Color::Yellow);
+            let mut controls = vec![
+                Span::styled("Press ", hint),
+                key_hint("q"),
+                Span::styled(" to quit. Use ", hint),
+                key_hint("↑"),
+                Span::styled("/", hint),
+                key_hint("↓"),
+                Span::styled(", ", hint),
+                key_hint("PgUp"),
+                Span::styled("/", hint),
+                key_hint("PgDn"),
+                Span::styled(", ", hint),
+                key_hint("Home"),
+                Span::styled("/", hint),
+                key_hint("End"),
+                Span::styled(" or the mouse wheel to scroll. ", hint),
+            ];
+            if search.editing || !search.query.is_empty() {
+                controls.extend([
+                    Span::styled(
+                        search.status(match_count),
+                        Style::default().fg(Color::Black).bg(Color::Yellow),
+                    ),
+                    Span::styled(" ", hint),
+                    key_hint("n"),
+                    Span::styled("/", hint),
+                    key_hint("N"),
+                    Span::styled(" next/previous.", hint),
+                ]);
+            } else {
+                controls.extend([key_hint("/"), Span::styled(" searches. ", hint)]);
+            }
+            let block_count = pane_blocks.iter().map(Vec::len).sum::<usize>();
+            controls.extend([key_hint("c"), Span::styled(" copies the response", hint)]);
+            if block_count > 0 {
+                let selected = match selected_block
+                    .and_then(|index| Some((index, nth_in_panes(&pane_blocks, index)?.1)))
+                {
+                    Some((index, block)) => format!(
+                        " (block {}/{}{})",
+                        index + 1,
+                        block_count,
+                        block
+                            .language
+                            .as_ref()
+                            .map(|language| format!(" {}", language))
+                            .unwrap_or_default()
+                    ),
+                    None => String::new(),
+                };
+                controls.extend([
+                    Span::styled(", ", hint),
+                    key_hint("["),
+                    Span::styled("/", hint),
+                    key_hint("]"),
+                    Span::styled(" selects a code block and ", hint),
+                    key_hint("C"),
+                    Span::styled(format!(" copies it{}.", selected), hint),
+                ]);
+            } else {
+                controls.push(Span::styled(".", hint));
+            }
+            if let Some((message, _)) = &notice {
+                controls.extend([
+                    Span::styled(" ", hint),
+                    Span::styled(message.as_str(), Style::default().fg(Color::Green)),
+                ]);
+            }
+            if !errors.is_empty() {
+                controls.extend([
+                    Span::styled(" ", hint),
+                    key_hint("e"),
+                    Span::styled(" shows/hides errors, ", hint),
+                    key_hint("Tab"),
+                    Span::styled(" switches pane, ", hint),
+                    key_hint("Enter"),
+                    Span::styled(" shows the selected error.", hint),
+                ]);
+            }
+            let controls_text = Line::from(controls);
             let controls_paragraph = Paragraph::new(controls_text)
                 .block(Block::default().borders(Borders::ALL).title("Controls"));
             f.render_widget(controls_paragraph, chunks[controls_index]);
+
+            if show_error_detail && let Some(entry) = errors.get(selected_error) {
+                let mut detail_text = vec![
+                    Line::from(vec![
+                        Span::styled("Time: ", Style::default().fg(Color::Yellow)),
+                        Span::raw(format!("{} UTC", logs::format_timestamp(entry.epoch))),
+                    ]),
+                    Line::from(vec![
+                        Span::styled("Run: ", Style::default().fg(Color::Yellow)),
+                        Span::raw(entry.run_id.clone().unwrap_or_default()),
+                    ]),
+                    Line::from(""),
+                ];
+                detail_text.extend(entry.message.lines().map(|line| {
+                    Line::from(Span::styled(
+                        line.to_string(),
+                        Style::default().fg(severity_color(entry)),
+                    ))
+                }));
+                let title = format!(
+                    "{} {} of {} · Esc to close",
+                    entry.level.unwrap_or(LogLevel::Error).as_str(),
+                    selected_error + 1,
+                    errors.len()
+                );
+                let detail_area = centered_rect(80, size.height * 2 / 3, size);
+                let detail_paragraph = Paragraph::new(detail_text)
+                    .block(Block::default().borders(Borders::ALL).title(title))
+                    .wrap(Wrap { trim: false });
+                f.render_widget(Clear, detail_area);
+                f.render_widget(detail_paragraph, detail_area);
+            }
+
+            if let Some(approval) = panes.iter().find_map(|pane| pane.pending_approval.as_ref()) {
+                let approval_text = vec![
+                    Line::from(vec![
+                        Span::styled("Tool: ", Style::default().fg(Color::Yellow)),
+                        Span::styled(
+                            approval.call.function.name.as_str(),
+                            Style::default().fg(Color::Green),
+                        ),
+                    ]),
+                    Line::from(vec![
+                        Span::styled("Arguments: ", Style::default().fg(Color::Yellow)),
+                        Span::raw(approval.call.function.arguments.as_str()),
+                    ]),
+                    Line::from(""),
+                    Line::from(vec![
+                        Span::styled("Allow this call? ", Style::default().fg(Color::Yellow)),
+                        Span::styled(
+                            "y",
+                            Style::default()
+                                .fg(Color::Green)
+                                .add_modifier(ratatui::style::Modifier::BOLD),
+                        ),
+                        Span::styled("/", Style::default().fg(Color::Yellow)),
+                        Span::styled(
+                            "n",
+                            Style::default()
+                                .fg(Color::Red)
+                                .add_modifier(ratatui::style::Modifier::BOLD),
+                        ),
+                    ]),
+                ];
+                let approval_area = centered_rect(60, 8, size);
+                let approval_paragraph = Paragraph::new(approval_text)
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .title("Tool call approval"),
+                    )
+                    .wrap(Wrap { trim: true });
+                f.render_widget(Clear, approval_area);
+                f.render_widget(approval_paragraph, approval_area);
+            }
         })?;
     }
 
     disable_raw_mode()?;
-    Ok(())
+    Ok(panes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stream_scroll_follows_until_scrolled_up() {
+        let mut scroll = StreamScroll::new();
+        scroll.update(10, 4);
+        assert_eq!(scroll.offset, 10);
+
+        scroll.scroll_by(-3);
+        scroll.update(25, 4);
+        assert_eq!((scroll.offset, scroll.follow), (7, false));
+
+        scroll.page_by(1);
+        scroll.scroll_by(100);
+        assert_eq!((scroll.offset, scroll.follow), (25, true));
+        scroll.update(30, 4);
+        assert_eq!(scroll.offset, 30);
+    }
+
+    #[test]
+    fn stream_scroll_is_clamped() {
+        let mut scroll = StreamScroll::new();
+        scroll.update(0, 20);
+        scroll.scroll_by(5);
+        assert_eq!((scroll.offset, scroll.follow), (0, true));
+
+        scroll.update(8, 20);
+        scroll.scroll_to_top();
+        scroll.page_by(-2);
+        assert_eq!((scroll.offset, scroll.follow), (0, false));
+
+        // the content got shorter, e.g. after the terminal was widened
+        scroll.scroll_to_bottom();
+        scroll.scroll_by(-1);
+        scroll.update(3, 20);
+        assert_eq!(scroll.offset, 3);
+        scroll.scroll_to_bottom();
+        assert!(scroll.follow);
+    }
 }